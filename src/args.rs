@@ -1,8 +1,15 @@
-use crate::args::Operation::AddFiles;
+use crate::args::Operation::{AddFiles, Export, Fsck, Import, Reconcile, Search, Sync, Tag};
 use anyhow::{Result, anyhow};
 use clap::{Parser, Subcommand};
 use std::env;
+use crate::geo::parse_point;
+use crate::keep::KeepPolicy;
+use crate::library::Format;
 use crate::order::Order;
+use crate::query::{Query, search_words};
+use crate::retag::{checked_label, replaced_tags};
+use crate::search::is_search_name;
+use crate::sync::SyncMode;
 use crate::path::{ABSOLUTE_PATH, check_file, check_reading_list_file, check_path, default_extract_list_file, standard_directory};
 
 const DEFAULT_WIDTH: i32   = 1000;
@@ -18,6 +25,99 @@ pub enum Operation {
         #[arg(long, value_name="DIRECTORY")]
         source_dir: Option<String>,
     },
+    /// export the pictures, tags and covers of the database to FILE
+    Export {
+        /// file format
+        #[arg(long, value_name="FORMAT", default_value_t = Format::Json)]
+        format: Format,
+        #[arg(value_name="FILE")]
+        file: String,
+    },
+    /// check the consistency of the pictures, tags and covers of the database
+    Fsck {
+        /// fix the inconsistencies that can be fixed
+        #[arg(long, default_value_t = false)]
+        repair: bool,
+    },
+    /// import the pictures, tags and covers exported to FILE (in json, or in csv if FILE ends with .csv) into the database
+    Import {
+        #[arg(value_name="FILE")]
+        file: String,
+        /// replace all the pictures, tags and covers of the database instead of merging with them
+        #[arg(long, default_value_t = false)]
+        replace: bool,
+    },
+    /// save, list or delete searches, i.e. combinations of the query, pattern, tag and order options
+    Search {
+        #[command(subcommand)]
+        action: SearchAction,
+    },
+    /// compare the pictures in the database with their IMAGE_DATA.json sidecar files and update one or the other
+    Sync {
+        #[arg(long, value_name="MODE", default_value_t = SyncMode::NewestWins)]
+        mode: SyncMode,
+    },
+    /// rename, merge or delete tags and labels in the whole library
+    Tag {
+        #[command(subcommand)]
+        action: TagAction,
+    },
+    /// match pictures missing from their database path with picture files moved or renamed in DIRECTORY (default directory is $GALLSHDIR)
+    Reconcile {
+        #[arg(long, value_name="DIRECTORY")]
+        source_dir: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum SearchAction {
+    /// save the query, pattern, select, include, tags-expr, exclude, order and origin options given as the search NAME
+    Save {
+        #[arg(value_name="NAME")]
+        name: String,
+    },
+    /// list the saved searches
+    List,
+    /// delete the saved search NAME
+    Delete {
+        #[arg(value_name="NAME")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum TagAction {
+    /// replace the tag or label OLD with NEW
+    Rename {
+        #[arg(value_name="OLD")]
+        old: String,
+        #[arg(value_name="NEW")]
+        new: String,
+    },
+    /// replace the tags or labels A B… with C, given as A B… into C
+    Merge {
+        #[arg(value_name="TAG", num_args = 3.., required = true)]
+        tags: Vec<String>,
+    },
+    /// remove the tag or label TAG from all the pictures
+    Delete {
+        #[arg(value_name="TAG")]
+        tag: String,
+    },
+    /// make ALIAS stand for TAG when adding tags in the gui
+    Alias {
+        #[arg(value_name="ALIAS")]
+        alias: String,
+        #[arg(value_name="TAG")]
+        tag: String,
+    },
+    /// delete the alias ALIAS
+    Unalias {
+        #[arg(value_name="ALIAS")]
+        alias: String,
+    },
+    /// list the aliases and the tags they stand for
+    Aliases,
 }
 
 #[derive(Parser, Clone, Debug)]
@@ -37,29 +137,57 @@ pub struct Args {
     pub file: Option<String>,
 
     /// display files that only contain STRING in their name
-    #[arg(short, long, value_name="STRING")]
+    #[arg(short, long, value_name="STRING", global = true)]
     pub pattern: Option<String>,
 
-    /// display files that match the query QUERY
-    #[arg(short, long, value_name="QUERY")]
+    /// display files that match the query QUERY, e.g. "rank>=2 and (tag:beach or label:travel) and not dir:~/images/2024"
+    #[arg(short, long, value_name="QUERY", global = true)]
     pub query: Option<String>,
 
-    /// select pictures having tags in the given list
-    #[arg(long, value_name="TAG_LIST")]
+    /// select pictures having tags in the given list, a tag such as place/france selecting place/france/paris as well
+    #[arg(long, value_name="TAG_LIST", global = true)]
     pub select: Option<Vec<String>>,
 
     /// select pictures having all the tags in the given list
-    #[arg(long, value_name="TAG_LIST")]
+    #[arg(long, value_name="TAG_LIST", global = true)]
     pub include: Option<Vec<String>>,
 
+    /// select pictures matching the tag expression TAGS_EXPR, e.g. "(beach or sea) and not blurry"
+    #[arg(long, value_name="TAGS_EXPR", global = true)]
+    pub tags_expr: Option<String>,
+
+    /// exclude pictures having any of the tags in the given list
+    #[arg(long, value_name="TAG_LIST", global = true)]
+    pub exclude: Option<Vec<String>>,
+
+    /// display pictures whose path, label, tags or note have words starting with the words of TEXT
+    #[arg(long, value_name="TEXT", global = true)]
+    pub search: Option<String>,
+
+    /// display the pictures of the album NAME, in the album order
+    #[arg(long, value_name="NAME")]
+    pub album: Option<String>,
+
+    /// display the pictures of the search saved as NAME
+    #[arg(long, value_name="NAME")]
+    pub saved: Option<String>,
+
+    /// use the library NAME defined in the config file instead of the default library
+    #[arg(long, value_name="NAME")]
+    pub library: Option<String>,
+
     /// list all directories of pictures in the database
     #[arg(long, default_value_t = false)]
 
     pub directories: bool,
     /// display pictures in order
-    #[arg(short, long, value_name="ORDER", ignore_case(true), default_value_t = Order::Random)]
+    #[arg(short, long, value_name="ORDER", ignore_case(true), default_value_t = Order::Random, global = true)]
     pub order: Order,
 
+    /// the point LAT,LON from which the Distance order is measured, the current picture if not given
+    #[arg(long, value_name="LAT,LON", allow_hyphen_values = true, global = true)]
+    pub origin: Option<String>,
+
     /// order pictures by Date
     #[arg(short, long, default_value_t = false)]
     pub date: bool,
@@ -119,14 +247,18 @@ pub struct Args {
     #[arg(long, default_value_t = false)]
     pub check: bool,
 
-    /// create the schema for the database
-    #[arg(long, default_value_t = false)]
-    pub create_schema: bool,
-
     /// move all duplicate files to TARGET_DIR
     #[arg(value_name = "TARGET_DIR")]
     pub deduplicate: Option<String>,
 
+    /// which copy of duplicate pictures to keep when deduplicating
+    #[arg(long, value_name="POLICY", default_value_t = KeepPolicy::HighestRank)]
+    pub keep: KeepPolicy,
+
+    /// list the groups of duplicate pictures, or the pictures differing from their sidecar, without changing any file
+    #[arg(long, default_value_t = false)]
+    pub dry_run: bool,
+
     /// remove entries from the database when file no longer exits
     #[arg(long, default_value_t = false)]
     pub purge: bool,
@@ -135,6 +267,18 @@ pub struct Args {
     #[arg(long, value_name="TARGET_DIR")]
     pub redirect: Option<String>,
 
+    /// show only pictures having near duplicates, grouped by similarity; THRESHOLD is the number of differing bits (0 to 64) of their perceptual hashes
+    #[arg(long, value_name="THRESHOLD")]
+    pub similar: Option<u32>,
+
+    /// write the IMAGE_DATA.json sidecar file of a picture each time it is changed
+    #[arg(long, default_value_t = false)]
+    pub write_sidecars: bool,
+
+    /// compute the content hash and read the EXIF metadata and dimensions of pictures in the database that don't have them
+    #[arg(long, default_value_t = false)]
+    pub rehash: bool,
+
     /// update picture data and thumbnails files
     #[arg(long, default_value_t = false)]
     pub update: bool,
@@ -155,14 +299,37 @@ impl Args {
                             Err(err) => return Err(err),
                         },
                         None => Some(AddFiles { source_dir: Some(standard_directory()) }),
-                    }
+                    },
+                    Export { format, file } => Some(Export { format, file }),
+                    Fsck { repair } => Some(Fsck { repair }),
+                    Import { file, replace } => Some(Import { file, replace }),
+                    Search { action: SearchAction::Save { name } } if !is_search_name(&name) => return Err(anyhow!(format!("search name {} should be made of lowercase letters, digits, - and _", name))),
+                    Search { action } => Some(Search { action }),
+                    Sync { mode } => Some(Sync { mode }),
+                    Tag { action: TagAction::Alias { alias, tag } } => match checked_label(&alias).and_then(|_| checked_label(&tag)) {
+                        Ok(_) => Some(Tag { action: TagAction::Alias { alias, tag } }),
+                        Err(err) => return Err(err),
+                    },
+                    Tag { action: action @ (TagAction::Unalias { .. } | TagAction::Aliases) } => Some(Tag { action }),
+                    Tag { action } => match replaced_tags(&action) {
+                        Ok(_) => Some(Tag { action }),
+                        Err(err) => return Err(err),
+                    },
+                    Reconcile { source_dir }  => match source_dir {
+                        Some(directory) => match check_path(&directory, ABSOLUTE_PATH) {
+                            Ok(_) => Some(Reconcile { source_dir: Some(directory) }),
+                            Err(err) => return Err(err),
+                        },
+                        None => Some(Reconcile { source_dir: Some(standard_directory()) }),
+                    },
                 },
             },
 
+            album: self.album.clone(),
+
             check: self.check,
 
             covers: self.covers,
-            create_schema: self.create_schema,
 
             date: self.date,
 
@@ -188,6 +355,10 @@ impl Args {
 
             directories: self.directories,
 
+            dry_run: self.dry_run,
+
+            library: self.library.clone(),
+
             list_extract: match &self.list_extract {
                 None => {
                     match default_extract_list_file() {
@@ -221,28 +392,21 @@ impl Args {
 
             height: Some(dimension(self.height, HEIGHT_ENV_VAR, "height", DEFAULT_HEIGHT)),
 
-            include: match self.include.clone() {
-                Some(list) => if !list.is_empty() {
-                    let tags:Vec<String> = list[0].split(' ').map(|s| s.into()).filter(|s:&String| !s.is_empty()).collect();
-                    if !tags.is_empty() {
-                        Some(tags)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                },
-                    None => None,
-            },
+            exclude: tag_list(self.exclude.clone()),
 
+            include: tag_list(self.include.clone()),
 
             info: self.info,
 
+            keep: self.keep,
+
             label: self.label.clone(),
 
             name: self.name,
 
-            order: if self.name {
+            order: if self.similar.is_some() || self.album.is_some() {
+                Order::Sequence
+            } else if self.name {
                 Order::Name
             } else if self.value {
                 Order::Value
@@ -252,11 +416,25 @@ impl Args {
                 self.order
             },
 
+            origin: match &self.origin {
+                None => None,
+                Some(source) => match parse_point(source) {
+                    Ok(_) => Some(source.to_string()),
+                    Err(err) => return Err(err),
+                },
+            },
+
             pattern: self.pattern.clone(),
 
             purge: self.purge,
 
-            query: self.query.clone(),
+            query: match &self.query {
+                None => None,
+                Some(source) => match Query::parse(source) {
+                    Ok(_) => Some(source.to_string()),
+                    Err(err) => return Err(err),
+                },
+            },
 
             redirect: match &self.redirect {
                 None => None,
@@ -266,24 +444,34 @@ impl Args {
                 },
             },
 
+            rehash: self.rehash,
+
+            saved: self.saved.clone(),
+
+            search: match &self.search {
+                Some(text) if search_words(text).is_empty() => return Err(anyhow!(format!("search text \"{}\" has no word", text))),
+                text => text.clone(),
+            },
+
             seconds: self.seconds,
 
-            select: match self.select.clone() {
-                Some(list) => if !list.is_empty() {
-                    let tags:Vec<String> = list[0].split(' ').map(|s| s.into()).filter(|s:&String| !s.is_empty()).collect();
-                    if !tags.is_empty() {
-                        Some(tags)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                },
-                    None => None,
+            select: tag_list(self.select.clone()),
+
+            similar: match self.similar {
+                Some(threshold) if threshold > 64 => return Err(anyhow!(format!("similarity threshold {} is not between 0 and 64", threshold))),
+                threshold => threshold,
             },
 
             tags: self.tags,
 
+            tags_expr: match &self.tags_expr {
+                None => None,
+                Some(source) => match Query::parse_tags(source) {
+                    Ok(_) => Some(source.to_string()),
+                    Err(err) => return Err(err),
+                },
+            },
+
             thumbnails: self.thumbnails,
 
             update: self.update,
@@ -291,11 +479,30 @@ impl Args {
             value: self.value,
 
             width: Some(dimension(self.width, WIDTH_ENV_VAR, "width", DEFAULT_WIDTH)),
+
+            write_sidecars: self.write_sidecars,
         };
         Ok(result)
     }
 }
 
+// tags given as a single space separated list
+fn tag_list(source: Option<Vec<String>>) -> Option<Vec<String>> {
+    match source {
+        Some(list) => if !list.is_empty() {
+            let tags:Vec<String> = list[0].split(' ').map(|s| s.into()).filter(|s:&String| !s.is_empty()).collect();
+            if !tags.is_empty() {
+                Some(tags)
+            } else {
+                None
+            }
+        } else {
+            None
+        },
+        None => None,
+    }
+}
+
 fn dimension(source: Option<i32>, var_name: &str, dimension_name: &str, default: i32) -> i32 {
     let candidate = match source {
         Some(n) => n,
@@ -5,6 +5,9 @@ use crate::comment::Comment;
 use crate::database::Database;
 use crate::display::{picture_info_display, title_display};
 use crate::editor::{Editor};
+use crate::geo::{distance_km, parse_point};
+use crate::history::HistoryEntry;
+use crate::keep::KeepPolicy;
 use crate::loader::load_picture_entries_from_source;
 use crate::navigator::Navigator;
 use crate::order::Order;
@@ -12,8 +15,12 @@ use crate::path::check_path;
 use crate::path::file_name;
 use crate::path::file_path_directory;
 use crate::picture_entry::{PictureEntries, PictureEntry};
-use crate::picture_io::{append_to_extract_file, copy_file_to_target_directory, delete_file, check_or_create_thumbnail_file};
+use crate::query::Query;
+use crate::perceptual::{clusters, PerceptualHash};
+use crate::picture_io::{append_to_extract_file, content_hash, copy_file_to_target_directory, delete_file, check_or_create_thumbnail_file, get_perceptual_hash_from_picture, write_image_data};
 use crate::rank::Rank;
+use crate::retag::{checked_label, retag};
+use crate::search::{Filters, saved_search_args};
 use rand::prelude::SliceRandom;
 use rand::thread_rng;
 use std::cmp::Ordering::{Less, Greater, Equal};
@@ -38,7 +45,10 @@ pub struct Catalog {
     picture_entries: Vec<PictureEntry>,
     previous_order: Option<Order>,
     selected_count: usize,
+    sequence: HashMap<String, usize>,
     pub tags: HashSet<String>,
+    tag_aliases: HashMap<String, String>,
+    text_matches: HashSet<String>,
 }
 
 impl Catalog {
@@ -56,10 +66,11 @@ impl Catalog {
             expand_on: false,
             order: Some(Order::Random),
             selected_count: 0,
+            sequence: HashMap::new(),
             previous_order: Some(Order::Random),
             args: None,
             discarded: Vec::new(),
-            database: match Database::initialize(false) {
+            database: match Database::initialize() {
                 Ok(database) => database,
                 Err(err) => {
                     eprintln!("{}", err);
@@ -67,6 +78,8 @@ impl Catalog {
                 }
             },
             tags: HashSet::new(),
+            tag_aliases: HashMap::new(),
+            text_matches: HashSet::new(),
         }
     }
 
@@ -82,6 +95,9 @@ impl Catalog {
         catalog.set_page_size(catalog.args.clone().unwrap().grid.unwrap());
         let picture_entries = load_picture_entries_from_source(&mut catalog.database, args);
         Catalog::set_picture_entries(&mut catalog, picture_entries)?;
+        if let Some(threshold) = args.similar {
+            catalog.cluster_similar(threshold)?
+        };
         catalog.count_selected();
         if catalog.navigator().length() == 0 {
             return Err(anyhow!("no picture to show"))
@@ -108,36 +124,177 @@ impl Catalog {
                 for label in labels {
                     self.tags.insert(label);
                 };
+            },
+            Err(err) => return Err(anyhow!(err)),
+        };
+        match self.database.select_tag_aliases() {
+            Ok(aliases) => {
+                self.tag_aliases = aliases.into_iter().collect();
                 Ok(())
             },
             Err(err) => Err(anyhow!(err)),
         }
     }
 
-    pub fn deduplicate_files(&mut self, target_dir: &str) -> Result<()> {
-        self.sort_by(Order::Size);
-         let mut prev_entry: Option<PictureEntry> = None;
-        for entry in &self.picture_entries {
-            if let Some(prev) = prev_entry {
-                match entry.equal_content(&prev) {
-                    Ok(true) => {
-                        println!("removing duplicate entry {}, same as {}", prev.original_file_path(), entry.original_file_path());
-                        match prev.copy_files(target_dir) {
-                            Ok(_) => match prev.delete_files() {
-                                Ok(()) => {},
-                                Err(err) => return Err(anyhow!(err)),
-                            },
-                            Err(err) => return Err(anyhow!(err)),
-                        }
+    /// the tags and the aliases that can be typed when adding a tag
+    pub fn tag_names(&self) -> HashSet<String> {
+        self.tags.iter().chain(self.tag_aliases.keys()).cloned().collect()
+    }
+
+    /// replace the tags and labels sources with target, or remove them if target is None, in the whole
+    /// library, updating the pictures of the catalog and the tags completed in the editor
+    pub fn rename_tags(&mut self, sources: &[String], target: Option<&str>) -> Result<usize> {
+        let picture_entries = retag(&mut self.database, sources, target)?;
+        for picture_entry in &picture_entries {
+            if let Some(entry) = self.picture_entries.iter_mut().find(|entry| entry.original_file_path() == picture_entry.file_path) {
+                entry.image_data.label = picture_entry.image_data.label.clone();
+                entry.image_data.tags = picture_entry.image_data.tags.clone()
+            }
+        };
+        self.initialize_tags()?;
+        Ok(picture_entries.len())
+    }
+
+    /// rename the label of the current picture in the whole library
+    pub fn rename_current_label(&mut self, label: &str) -> Result<()> {
+        let label = checked_label(label)?;
+        match self.current_entry().and_then(|entry| entry.label()) {
+            Some(old) => self.rename_tags(&[old], Some(&label)).map(|count| println!("{} pictures retagged", count)),
+            None => Err(anyhow!("current picture has no label to rename")),
+        }
+    }
+
+    // groups of indices of entries having the same content, found by size then content hash
+    fn duplicate_groups(&self) -> Result<Vec<Vec<usize>>> {
+        let mut by_size: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (index, entry) in self.picture_entries.iter().enumerate() {
+            by_size.entry(entry.file_size).or_default().push(index)
+        };
+        let mut groups: Vec<Vec<usize>> = vec![];
+        for indices in by_size.into_values().filter(|indices| indices.len() > 1) {
+            let mut by_hash: HashMap<String, Vec<usize>> = HashMap::new();
+            for index in indices {
+                let entry = &self.picture_entries[index];
+                let hash = match &entry.content_hash {
+                    Some(hash) => hash.clone(),
+                    None => match content_hash(&entry.original_file_path()) {
+                        Ok(hash) => hash,
+                        Err(err) => return Err(anyhow!(err)),
                     },
-                    Ok(false) => {} ,
-                    Err(err) => return Err(anyhow!(err)),
+                };
+                by_hash.entry(hash).or_default().push(index)
+            };
+            groups.extend(by_hash.into_values().filter(|group| group.len() > 1))
+        };
+        groups.sort_by(|a, b| self.picture_entries[a[0]].file_path.cmp(&self.picture_entries[b[0]].file_path));
+        Ok(groups)
+    }
+
+    /// move all duplicate files but one per group to target_dir, merging labels and tags of the copies into the one kept
+    pub fn deduplicate_files(&mut self, target_dir: &str, keep: KeepPolicy, dry_run: bool) -> Result<()> {
+        let groups = match self.duplicate_groups() {
+            Ok(groups) => groups,
+            Err(err) => return Err(anyhow!(err)),
+        };
+        if groups.is_empty() {
+            println!("no duplicate found");
+            return Ok(())
+        };
+        let mut removed: HashSet<usize> = HashSet::new();
+        for group in groups {
+            let entries: Vec<PictureEntry> = group.iter().map(|&index| self.picture_entries[index].clone()).collect();
+            let survivor_index = keep.survivor(&entries);
+            let mut survivor = entries[survivor_index].clone();
+            println!("keeping {} ({})", survivor.original_file_path(), keep);
+            for (index, entry) in entries.iter().enumerate() {
+                if index == survivor_index {
+                    continue
+                };
+                println!("  removing duplicate {}", entry.original_file_path());
+                // the survivor keeps its label, other labels become tags
+                if let Some(label) = entry.label() {
+                    if survivor.label().is_none() {
+                        survivor.image_data.label = label
+                    } else if survivor.label() != Some(label.clone()) {
+                        survivor.image_data.tags.insert(label);
+                    }
+                };
+                survivor.image_data.tags.extend(entry.image_data.tags.iter().cloned());
+                // a copy that was the cover of its directory hands its cover over to the survivor
+                survivor.image_data.cover |= entry.image_data.cover;
+            };
+            if dry_run {
+                continue
+            };
+            for (index, entry) in entries.iter().enumerate() {
+                if index == survivor_index {
+                    continue
+                };
+                if let Err(err) = entry.copy_files(target_dir).and_then(|_| entry.delete_files()) {
+                    return Err(anyhow!(err))
                 }
             };
-            prev_entry = Some(entry.clone());
+            self.database.begin_transaction()?;
+            let result = self.remove_duplicates(&entries, survivor_index, &survivor);
+            self.database.end_transaction(result)?;
+            removed.extend(group.iter().enumerate().filter(|(index, _)| *index != survivor_index).map(|(_, &index)| index));
+            self.picture_entries[group[survivor_index]] = survivor;
+        };
+        let picture_entries: PictureEntries = self.picture_entries.iter().enumerate()
+            .filter(|(index, _)| !removed.contains(index))
+            .map(|(_, entry)| entry.clone())
+            .collect();
+        self.set_picture_entries(Ok(picture_entries))
+    }
+
+    // delete the rows of the duplicates of a group, with their cover, and update the survivor
+    fn remove_duplicates(&mut self, entries: &[PictureEntry], survivor_index: usize, survivor: &PictureEntry) -> Result<()> {
+        for (index, entry) in entries.iter().enumerate() {
+            if index == survivor_index {
+                continue
+            };
+            self.database.delete_picture(&entry.original_file_path())?;
+            if entry.image_data.cover {
+                self.database.delete_cover(&file_path_directory(&entry.file_path), &file_name(&entry.file_path))?
+            }
+        };
+        if survivor.image_data.cover && !entries[survivor_index].image_data.cover {
+            self.database.insert_or_update_cover(&file_path_directory(&survivor.file_path), &file_name(&survivor.file_path), survivor.image_data.rank)?
+        };
+        if survivor.image_data != entries[survivor_index].image_data {
+            self.database.update_picture_entry(survivor)?
         };
         Ok(())
     }
+
+    /// keep only the pictures having near duplicates, cluster by cluster, computing the missing perceptual hashes
+    pub fn cluster_similar(&mut self, threshold: u32) -> Result<()> {
+        // the hashes are computed before the transaction, so that the database is locked only while updating
+        let mut hashed: Vec<usize> = vec![];
+        for index in 0..self.picture_entries.len() {
+            if self.picture_entries[index].image_data.perceptual_hash.is_none() {
+                let file_path = self.picture_entries[index].original_file_path();
+                match get_perceptual_hash_from_picture(&file_path) {
+                    Ok(hash) => {
+                        self.picture_entries[index].image_data.perceptual_hash = Some(hash);
+                        hashed.push(index)
+                    },
+                    Err(err) => eprintln!("{}", err),
+                }
+            }
+        };
+        self.database.begin_transaction()?;
+        let result = hashed.iter().try_for_each(|&index| self.database.update_picture_entry(&self.picture_entries[index]));
+        self.database.end_transaction(result)?;
+        let hashes: Vec<Option<PerceptualHash>> = self.picture_entries.iter().map(|entry| entry.image_data.perceptual_hash).collect();
+        let groups = clusters(&hashes, threshold);
+        println!("{} clusters of similar pictures", groups.len());
+        let picture_entries: PictureEntries = groups.iter()
+            .flat_map(|group| group.iter().map(|&index| self.picture_entries[index].clone()))
+            .collect();
+        self.set_picture_entries(Ok(picture_entries))
+    }
+
     pub fn update_files(&self) -> Result<()> {
         let mut update_result = Ok(());
         for entry in &self.picture_entries {
@@ -344,9 +501,12 @@ impl Catalog {
         self.navigator.index().and_then(|index| { self.entry_at_index(index) } )
     }
 
-    pub fn find_index_input_pattern(&mut self, pattern: &str) -> Option<usize> {
-        self.picture_entries.iter().position(|entry|
-            entry.original_file_path().contains(pattern))
+    // the first index after start, in the given direction and wrapping around, of a picture matching the last text search
+    fn find_index_text_match(&self, start: usize, forward: bool) -> Option<usize> {
+        let length = self.picture_entries.len();
+        (1..=length)
+            .map(|offset| if forward { (start + offset) % length } else { (start + length - offset % length) % length })
+            .find(|&index| self.text_matches.contains(&self.picture_entries[index].original_file_path()))
     }
 
     pub fn find_index_label_search(&mut self, pattern: &str) -> Option<usize> {
@@ -378,20 +538,126 @@ impl Catalog {
     }
     // updates
 
-    pub fn set_current_picture_entry(&mut self, picture_entry: PictureEntry) -> Result<()> {
+    pub fn set_current_picture_entry(&mut self, picture_entry: PictureEntry, comment: &Comment) -> Result<()> {
         match self.navigator.index() {
-            Some(index) => {
-                self.picture_entries[index] = picture_entry.clone();
-                self.database.update_picture_entry(&picture_entry)
-            },
+            Some(index) => self.apply_comment(comment, vec![(index, picture_entry)]),
             None => Ok(())
         }
     }
 
+    // replace the picture entries at the given indices and update them in the database in a
+    // single transaction, recording the changes as a single history batch
+    fn apply_comment(&mut self, comment: &Comment, changes: Vec<(usize, PictureEntry)>) -> Result<()> {
+        let history: Vec<HistoryEntry> = changes.iter()
+            .map(|(index, picture_entry)| HistoryEntry::new(comment, &self.picture_entries[*index], picture_entry))
+            .collect();
+        self.database.begin_transaction()?;
+        let result = changes.iter()
+            .try_for_each(|(_, picture_entry)| self.database.update_picture_entry(picture_entry))
+            .and_then(|_| self.database.insert_history_batch(&history));
+        self.database.end_transaction(result)?;
+        for (index, picture_entry) in changes {
+            self.write_sidecar(&picture_entry)?;
+            self.picture_entries[index] = picture_entry
+        };
+        Ok(())
+    }
+
+    // keep the IMAGE_DATA.json file of a picture up to date, if asked for
+    fn write_sidecar(&self, picture_entry: &PictureEntry) -> Result<()> {
+        match &self.args {
+            Some(args) if args.write_sidecars => write_image_data(&picture_entry.image_data, &picture_entry.image_data_file_path()),
+            _ => Ok(()),
+        }
+    }
+
+    /// restore the pictures changed by the last batch of comments
+    pub fn undo(&mut self) -> Result<()> {
+        self.restore_history_batch(true)
+    }
+
+    /// apply again the last batch of comments undone
+    pub fn redo(&mut self) -> Result<()> {
+        self.restore_history_batch(false)
+    }
+
+    fn restore_history_batch(&mut self, undo: bool) -> Result<()> {
+        let (batch, mut history) = match self.database.select_history_batch(undo) {
+            Ok(Some(result)) => result,
+            Ok(None) => {
+                println!("nothing to {}", if undo { "undo" } else { "redo" });
+                return Ok(())
+            },
+            Err(err) => return Err(anyhow!(err)),
+        };
+        if undo {
+            history.reverse()
+        };
+        self.database.begin_transaction()?;
+        let result = self.restore_history_entries(&history, undo)
+            .and_then(|restored| self.database.mark_history_batch(batch, undo).map(|_| restored));
+        let restored = self.database.end_transaction(result)?;
+        for (index, picture_entry) in restored {
+            self.write_sidecar(&picture_entry)?;
+            if let Some(index) = index {
+                self.picture_entries[index] = picture_entry
+            }
+        };
+        println!("{} {} on {} pictures", if undo { "undone" } else { "redone" }, history[0].comment, history.len());
+        self.count_selected();
+        self.refresh();
+        Ok(())
+    }
+
+    // update the database with the pictures as they were before (undo) or after the history entries,
+    // returning the restored entries with their index in the catalog, which is left as it is until
+    // the transaction is committed
+    fn restore_history_entries(&mut self, history: &[HistoryEntry], undo: bool) -> Result<Vec<(Option<usize>, PictureEntry)>> {
+        let mut restored: Vec<(Option<usize>, PictureEntry)> = vec![];
+        for entry in history {
+            let (image_data, deleted) = entry.restored(undo);
+            let index = self.picture_entries.iter().position(|picture_entry| picture_entry.file_path == entry.file_path);
+            let already_restored = restored.iter().rev()
+                .find(|(_, picture_entry)| picture_entry.file_path == entry.file_path)
+                .map(|(_, picture_entry)| picture_entry.clone());
+            let mut picture_entry = match (already_restored, index) {
+                (Some(picture_entry), _) => picture_entry,
+                (None, Some(index)) => self.picture_entries[index].clone(),
+                (None, None) => match self.database.select_picture_entry(&entry.file_path) {
+                    Ok(Some(picture_entry)) => picture_entry,
+                    Ok(None) => continue,
+                    Err(err) => return Err(anyhow!(err)),
+                },
+            };
+            if image_data.cover != picture_entry.image_data.cover {
+                let dir_path = file_path_directory(&picture_entry.file_path);
+                let file_name = file_name(&picture_entry.file_path);
+                let result = if image_data.cover {
+                    self.database.insert_or_update_cover(&dir_path, &file_name, image_data.rank)
+                } else {
+                    self.database.delete_cover(&dir_path, &file_name)
+                };
+                if let Err(err) = result {
+                    return Err(anyhow!(err))
+                }
+            };
+            picture_entry.image_data = image_data;
+            picture_entry.deleted = deleted;
+            match self.database.update_picture_entry(&picture_entry) {
+                Ok(()) => restored.push((index, picture_entry)),
+                Err(err) => return Err(anyhow!(err)),
+            }
+        };
+        Ok(restored)
+    }
+
     pub fn set_picture_entries(&mut self, picture_entries_result: Result<PictureEntries>) -> Result<()> {
         match picture_entries_result {
             Ok(picture_entries) => {
                 self.picture_entries = picture_entries;
+                self.sequence = self.picture_entries.iter().enumerate()
+                    .map(|(index, entry)| (entry.original_file_path(), index))
+                    .collect();
                 self.navigator.set_length(self.picture_entries.len());
                 Ok(())
             },
@@ -399,6 +665,121 @@ impl Catalog {
         }
     }
 
+    /// the names of the albums
+    pub fn album_names(&self) -> HashSet<String> {
+        match self.database.select_album_names() {
+            Ok(names) => names,
+            Err(err) => {
+                eprintln!("{}", err);
+                HashSet::new()
+            },
+        }
+    }
+
+    /// add the selected pictures, or the current picture if none is selected, at the end of the album
+    pub fn add_to_album(&mut self, album: &str) -> Result<()> {
+        let file_paths: Vec<String> = if self.selected_count > 0 {
+            self.picture_entries.iter()
+                .filter(|entry| entry.image_data.selected)
+                .map(|entry| entry.original_file_path())
+                .collect()
+        } else {
+            self.current_entry().map(|entry| entry.original_file_path()).into_iter().collect()
+        };
+        let count = self.database.add_to_album(album, &file_paths)?;
+        println!("{} pictures added to album {}", count, album);
+        Ok(())
+    }
+
+    /// move the current picture one place backward or forward in the album being shown
+    pub fn move_current_entry_in_album(&mut self, forward: bool) -> Result<()> {
+        let album = match self.args.as_ref().and_then(|args| args.album.clone()) {
+            Some(album) => album,
+            None => return Err(anyhow!("pictures can only be moved when showing an album")),
+        };
+        if self.order != Some(Order::Sequence) {
+            return Err(anyhow!("pictures can only be moved in the album order"))
+        };
+        let index = match self.navigator.index() {
+            Some(index) => index,
+            None => return Err(anyhow!("empty catalog")),
+        };
+        let other = match (forward, index) {
+            (false, 0) => return Ok(()),
+            (false, _) => index - 1,
+            (true, _) if index + 1 >= self.picture_entries.len() => return Ok(()),
+            (true, _) => index + 1,
+        };
+        let file_path = self.picture_entries[index].original_file_path();
+        let other_file_path = self.picture_entries[other].original_file_path();
+        self.database.swap_album_positions(&album, &file_path, &other_file_path)?;
+        self.picture_entries.swap(index, other);
+        self.sequence.insert(file_path, other);
+        self.sequence.insert(other_file_path, index);
+        self.navigator.move_to_index(other);
+        Ok(())
+    }
+
+    /// the names of the saved searches
+    pub fn saved_search_names(&self) -> HashSet<String> {
+        match self.database.select_saved_searches() {
+            Ok(searches) => searches.into_iter().map(|(name, _)| name).collect(),
+            Err(err) => {
+                eprintln!("{}", err);
+                HashSet::new()
+            },
+        }
+    }
+
+    /// replace the pictures of the catalog with those of the search saved as name
+    pub fn load_saved_search(&mut self, name: &str) -> Result<()> {
+        let args = match &self.args {
+            Some(args) => saved_search_args(&self.database, name, args)?,
+            None => return Err(anyhow!("catalog not initialized")),
+        };
+        self.load_args(args, &format!("saved search {}", name))
+    }
+
+    /// replace the pictures of the catalog with those taken within radius kilometers of the
+    /// current picture, nearest first
+    pub fn show_near_current_entry(&mut self, radius: f64) -> Result<()> {
+        let (latitude, longitude) = match self.current_entry() {
+            Some(entry) => match entry.exif.location() {
+                Some(location) => location,
+                None => return Err(anyhow!(format!("{} has no location", entry.original_file_path()))),
+            },
+            None => return Err(anyhow!("empty catalog")),
+        };
+        let args = match &self.args {
+            Some(args) => Filters {
+                query: Some(format!("near:{},{},{}", latitude, longitude, radius)),
+                order: Some(Order::Distance.to_string()),
+                origin: Some(format!("{},{}", latitude, longitude)),
+                ..Filters::default()
+            }.applied_to(args)?,
+            None => return Err(anyhow!("catalog not initialized")),
+        };
+        self.load_args(args, &format!("{} km around the picture", radius))
+    }
+
+    // replace the pictures of the catalog with those selected by args
+    fn load_args(&mut self, args: Args, what: &str) -> Result<()> {
+        let picture_entries = load_picture_entries_from_source(&mut self.database, &args)?;
+        if picture_entries.is_empty() {
+            return Err(anyhow!(format!("no picture to show in {}", what)))
+        };
+        self.set_picture_entries(Ok(picture_entries))?;
+        self.navigator.move_to_first_index();
+        if let Some(threshold) = args.similar {
+            self.cluster_similar(threshold)?
+        };
+        self.count_selected();
+        self.sort_by(args.order);
+        self.navigator.move_to_first_index();
+        self.args = Some(args);
+        Ok(())
+    }
+
     pub fn cover_current_entry(&mut self) -> Result<()> {
         match self.current_entry() {
             Some(picture_entry) => {
@@ -410,7 +791,7 @@ impl Catalog {
                 match self.database.insert_or_update_cover(&dir_path, &file_name, rank) {
                     Ok(()) => {
                         self.last_comment = Some(Comment::Cover);
-                        self.set_current_picture_entry(new_picture_entry)
+                        self.set_current_picture_entry(new_picture_entry, &Comment::Cover)
                     },
                     Err(err) => Err(anyhow!(err)),
                 }
@@ -428,7 +809,7 @@ impl Catalog {
                 match self.database.delete_cover(&dir_path, &file_name) {
                     Ok(()) => {
                         self.last_comment = Some(Comment::Uncover);
-                        self.set_current_picture_entry(new_picture_entry)
+                        self.set_current_picture_entry(new_picture_entry, &Comment::Uncover)
                     },
                     Err(err) => Err(anyhow!(err)),
                 }
@@ -441,7 +822,7 @@ impl Catalog {
             Some(picture_entry) => {
                 let mut new_picture_entry = picture_entry.clone();
                 new_picture_entry.set_label(label);
-                match self.set_current_picture_entry(new_picture_entry) {
+                match self.set_current_picture_entry(new_picture_entry, &Comment::Label { label: label.to_string() }) {
                     Ok(()) => {
                         self.copied_label = Some(label.to_string());
                         self.tags.insert(label.to_string());
@@ -455,13 +836,27 @@ impl Catalog {
         }
     }
 
+    /// replace the note of the current picture, an empty note removing it
+    pub fn note_current_entry(&mut self, note: &str) -> Result<()> {
+        match self.current_entry() {
+            Some(picture_entry) => {
+                let mut new_picture_entry = picture_entry.clone();
+                new_picture_entry.set_note(note);
+                let comment = Comment::Note { note: new_picture_entry.image_data.note.clone() };
+                self.set_current_picture_entry(new_picture_entry, &comment)
+                    .map(|_| self.last_comment = Some(comment))
+            },
+            None => Ok(()),
+        }
+    }
+
     pub fn paste_label_current_entry(&mut self) -> Result<()> {
         match self.copied_label() {
             Some(label) => match self.current_entry() {
                 Some(picture_entry) => {
                     let mut new_picture_entry = picture_entry.clone();
                     new_picture_entry.set_label(&label);
-                    match self.set_current_picture_entry(new_picture_entry) {
+                    match self.set_current_picture_entry(new_picture_entry, &Comment::Label { label: label.clone() }) {
                         Ok(()) => {
                             self.last_comment = Some(Comment::Label { label } );
                             Ok(())
@@ -476,11 +871,16 @@ impl Catalog {
     }
 
     pub fn tag_current_entry(&mut self, tag: &str) -> Result<()> {
+        let tag = match self.tag_aliases.get(tag) {
+            Some(aliased) => aliased.clone(),
+            None => tag.to_string(),
+        };
+        let tag = tag.as_str();
         match self.current_entry() {
             Some(picture_entry) => {
                 let mut new_picture_entry = picture_entry.clone();
                 new_picture_entry.add_tag(tag);
-                match self.set_current_picture_entry(new_picture_entry) {
+                match self.set_current_picture_entry(new_picture_entry, &Comment::AddTag { label: tag.to_string() }) {
                     Ok(()) => {
                         self.tags.insert(tag.to_string());
                         self.last_comment = Some(Comment::AddTag { label: tag.to_string() });
@@ -498,7 +898,7 @@ impl Catalog {
             Some(picture_entry) => {
                 let mut new_picture_entry = picture_entry.clone();
                 new_picture_entry.delete_tag(tag);
-                match self.set_current_picture_entry(new_picture_entry) {
+                match self.set_current_picture_entry(new_picture_entry, &Comment::DeleteTag { label: tag.to_string() }) {
                     Ok(()) => {
                         self.tags.insert(tag.to_string());
                         self.last_comment = Some(Comment::DeleteTag { label: tag.to_string() });
@@ -516,7 +916,7 @@ impl Catalog {
             Some(picture_entry) => {
                 let mut new_picture_entry = picture_entry.clone();
                 new_picture_entry.image_data.selected = !new_picture_entry.image_data.selected;
-                match self.set_current_picture_entry(new_picture_entry) {
+                match self.set_current_picture_entry(new_picture_entry, &Comment::ToggleSelect) {
                     Ok(()) => {
                         self.last_comment = Some(Comment::ToggleSelect);
                         Ok(())
@@ -533,7 +933,7 @@ impl Catalog {
             Some(picture_entry) => {
                 let mut new_picture_entry = picture_entry.clone();
                 new_picture_entry.deleted = !new_picture_entry.deleted;
-                match self.set_current_picture_entry(new_picture_entry) {
+                match self.set_current_picture_entry(new_picture_entry, &Comment::ToggleDelete) {
                     Ok(()) => {
                         self.last_comment = Some(Comment::ToggleDelete);
                         Ok(())
@@ -571,11 +971,26 @@ impl Catalog {
         self.navigator.set_new_page_size(page_size)
     }
 
-    pub fn move_to_input_pattern(&mut self, pattern: &str) {
-        if let Some(index) = self.find_index_input_pattern(pattern) {
-            if self.navigator.can_move_to_index(index) {
-                self.navigator.move_to_index(index)
-            }
+    /// search the full text index for text and move to the first matching picture from the current one
+    pub fn search_text(&mut self, text: &str) -> Result<()> {
+        let picture_entries = self.database.select_pictures(&Query::Text { text: text.to_string() })?;
+        self.text_matches = picture_entries.iter().map(|entry| entry.file_path.clone()).collect();
+        if self.text_matches.is_empty() {
+            return Err(anyhow!(format!("no picture matching {}", text)))
+        };
+        if let Some(entry) = self.current_entry() && self.text_matches.contains(&entry.original_file_path()) {
+            return Ok(())
+        };
+        self.move_to_next_match(true);
+        Ok(())
+    }
+
+    /// move to the next or previous picture matching the last text search
+    pub fn move_to_next_match(&mut self, forward: bool) {
+        if let Some(start) = self.navigator.index()
+            && let Some(index) = self.find_index_text_match(start, forward)
+            && self.navigator.can_move_to_index(index) {
+            self.navigator.move_to_index(index)
         }
     }
 
@@ -604,7 +1019,7 @@ impl Catalog {
             Some(picture_entry) => {
                 let mut new_picture_entry = picture_entry.clone();
                 new_picture_entry.set_rank(rank);
-                match self.set_current_picture_entry(new_picture_entry) {
+                match self.set_current_picture_entry(new_picture_entry, &Comment::Rank { rank }) {
                     Ok(()) => {
                         self.last_comment = Some(Comment::Rank { rank });
                         Ok(())
@@ -646,22 +1061,20 @@ impl Catalog {
         
     pub fn print_labels_all(&mut self) -> Result<()> {
         let mut tags:HashMap<String,usize> = HashMap::new();
-
-        for i in 0..self.picture_entries.len() {
-            self.navigator = self.navigator.set_index(i);
-            let entry = &self.picture_entries[i];
+        let tags_by_file_path = match self.database.select_tags_by_file_path() {
+            Ok(tags_by_file_path) => tags_by_file_path,
+            Err(err) => return Err(anyhow!(err)),
+        };
+        for entry in &self.picture_entries {
             if entry.label().is_some() {
                 let stat = tags.entry(entry.label().unwrap().clone()).or_insert(0);
                 *stat += 1;
             }
-            match self.database.entry_tags(&entry.file_path) {
-                Ok(labels) => {
-                    for label in labels {
-                        let stat = tags.entry(label.to_string()).or_insert(0);
-                        *stat += 1;
-                    }
-                },
-                Err(err) => return Err(anyhow!(err)),
+            if let Some(labels) = tags_by_file_path.get(&entry.file_path) {
+                for label in labels {
+                    let stat = tags.entry(label.to_string()).or_insert(0);
+                    *stat += 1;
+                }
             }
         };
         let mut stats:Vec<(usize,String)> = vec![];
@@ -697,6 +1110,7 @@ impl Catalog {
             None => Ok(()),
             Some(Comment::Label { label }) => self.label_current_entry(&label),
             Some(Comment::Unlabel) => self.unlabel_current_entry(),
+            Some(Comment::Note { note }) => self.note_current_entry(&note),
             Some(Comment::AddTag { label}) => self.tag_current_entry(&label),
             Some(Comment::DeleteTag { label}) => self.untag_current_entry(&label),
             Some(Comment::Rank { rank }) => self.rank_current_entry(rank),
@@ -708,17 +1122,15 @@ impl Catalog {
     }
 
     pub fn set_selected_labels_with_input(&mut self, label: &str) -> Result<()> {
-        for index in 0..self.picture_entries.len() {
-            let entry = &mut self.picture_entries[index];
+        let mut changes: Vec<(usize, PictureEntry)> = vec![];
+        for (index, entry) in self.picture_entries.iter().enumerate() {
             if entry.image_data.selected {
+                let mut entry = entry.clone();
                 entry.set_label(label);
-                match self.database.update_picture_entry(entry) {
-                    Ok(()) => {},
-                    Err(err) => return Err(anyhow!(err)),
-                }
+                changes.push((index, entry))
             }
         };
-        Ok(())
+        self.apply_comment(&Comment::Label { label: label.to_string() }, changes)
     }
 
     pub fn copy_label(&mut self) {
@@ -740,7 +1152,7 @@ impl Catalog {
             Some(picture_entry) => {
                 let mut new_picture_entry = picture_entry.clone();
                 new_picture_entry.unlabel();
-                match self.set_current_picture_entry(new_picture_entry) {
+                match self.set_current_picture_entry(new_picture_entry, &Comment::Unlabel) {
                     Ok(()) => {
                         self.last_comment = Some(Comment::Unlabel);
                         Ok(())
@@ -759,30 +1171,28 @@ impl Catalog {
                     None => self.repeat_last_comment(),
                     Some(other) => {
                         let (start,end) = if other <= index { (other,index) } else { (index,other) };
-                        match &self.last_comment {
-                            None => {},
-                            Some(comment) => { println!("[{}…{}] {}", start, end, comment); },
-                        };
-                        for i in start..end+1 {
-                            let entry: &mut PictureEntry = &mut self.picture_entries[i];
-                            match &self.last_comment {
-                                None => {},
-                                Some(Comment::Label { label }) => entry.set_label(label),
-                                Some(Comment::Unlabel) => entry.unlabel(),
-                                Some(Comment::AddTag { label}) => entry.add_tag(label),
-                                Some(Comment::DeleteTag { label}) => entry.delete_tag(label),
-                                Some(Comment::Rank { rank }) => entry.set_rank(*rank),
-                                Some(Comment::ToggleSelect) => { entry.image_data.selected = !entry.image_data.selected }
-                                Some(Comment::ToggleDelete) => { entry.deleted = !entry.deleted },
-                                Some(Comment::Cover) => { entry.image_data.cover = true },
-                                Some(Comment::Uncover) => { entry.image_data.cover = false },
-                            };
-                            if self.last_comment.is_some() {
-                                match self.database.update_picture_entry(&entry.clone()) {
-                                    Ok(()) => {},
-                                    Err(err) => return Err(anyhow!(err)),
-                                }
+                        if let Some(comment) = self.last_comment.clone() {
+                            println!("[{}…{}] {}", start, end, comment);
+                            let mut changes: Vec<(usize, PictureEntry)> = vec![];
+                            for i in start..end+1 {
+                                let mut entry: PictureEntry = self.picture_entries[i].clone();
+                                match &comment {
+                                    Comment::Label { label } => entry.set_label(label),
+                                    Comment::Unlabel => entry.unlabel(),
+                                    Comment::Note { note } => entry.set_note(note),
+                                    Comment::AddTag { label} => entry.add_tag(label),
+                                    Comment::DeleteTag { label} => entry.delete_tag(label),
+                                    Comment::Rank { rank } => entry.set_rank(*rank),
+                                    Comment::ToggleSelect => { entry.image_data.selected = !entry.image_data.selected }
+                                    Comment::ToggleDelete => { entry.deleted = !entry.deleted },
+                                    Comment::Cover => { entry.image_data.cover = true },
+                                    Comment::Uncover => { entry.image_data.cover = false },
+                                };
+                                changes.push((i, entry))
                             };
+                            if let Err(err) = self.apply_comment(&comment, changes) {
+                                return Err(anyhow!(err))
+                            }
                         };
                         self.navigator = self.navigator.cancel_set();
                         Ok(())
@@ -798,16 +1208,7 @@ impl Catalog {
             Some(_) => {
                 let start = self.navigator.page_index();
                 let end = start + self.page_length();
-                for i in start..end {
-                    let entry: &mut PictureEntry = &mut self.picture_entries[i];
-                    entry.image_data.selected = false;
-                    match self.database.update_picture_entry(entry) {
-                        Ok(()) => {},
-                        Err(err) => return Err(anyhow!(err)),
-                    }
-                };
-                self.count_selected();
-                Ok(())
+                self.unselect_range(start, end)
             },
             None => Err(anyhow!("empty catalog")),
         }
@@ -815,40 +1216,64 @@ impl Catalog {
 
     pub fn unselect_all(&mut self) -> Result<()> {
         match self.navigator.index() {
-            Some(_) => {
-                let start = 0;
-                let end = self.navigator.length();
-                for i in start..end {
-                    let entry: &mut PictureEntry = &mut self.picture_entries[i];
-                    entry.image_data.selected = false;
-                    match self.database.update_picture_entry(entry) {
-                        Ok(()) => {},
-                        Err(err) => return Err(anyhow!(err)),
-                    }
-                };
-                self.count_selected();
-                Ok(())
-            },
+            Some(_) => self.unselect_range(0, self.navigator.length()),
             None => Err(anyhow!("empty catalog")),
         }
     }
 
+    // unselect the selected pictures from start to end, updating the database in a single transaction
+    fn unselect_range(&mut self, start: usize, end: usize) -> Result<()> {
+        let indices: Vec<usize> = (start..end.min(self.picture_entries.len()))
+            .filter(|&index| self.picture_entries[index].image_data.selected)
+            .collect();
+        self.database.begin_transaction()?;
+        let result = indices.iter().try_for_each(|&index| {
+            let mut entry = self.picture_entries[index].clone();
+            entry.image_data.selected = false;
+            self.database.update_picture_entry(&entry)
+        });
+        self.database.end_transaction(result)?;
+        for index in indices {
+            self.picture_entries[index].image_data.selected = false
+        };
+        self.count_selected();
+        Ok(())
+    }
+
     pub fn count_selected(&mut self) {
         self.selected_count = self.picture_entries.clone().iter().filter(|entry| entry.image_data.selected).count()
     }
 
+    /// the point the Distance order is measured from: the --origin option, or else the current picture
+    fn distance_origin(&self) -> Option<(f64, f64)> {
+        self.args.as_ref()
+            .and_then(|args| args.origin.as_deref())
+            .and_then(|source| parse_point(source).ok())
+            .or_else(|| self.current_entry().and_then(|entry| entry.exif.location()))
+    }
+
     pub fn sort_by(&mut self, order: Order) {
+        let origin = self.distance_origin();
         if let Some(entry) = self.current_entry() {
             let original_file_path = entry.original_file_path();
+            // pictures without a location are the farthest
+            let distance = |entry: &PictureEntry| entry.exif.location().zip(origin)
+                .map(|((latitude, longitude), (origin_latitude, origin_longitude))| distance_km(origin_latitude, origin_longitude, latitude, longitude))
+                .unwrap_or(f64::INFINITY);
             match order {
                 Order::Colors => self.picture_entries.sort_by(|a, b| { a.image_data.colors.cmp(&b.image_data.colors) }),
                 Order::Date => self.picture_entries.sort_by(|a, b| { a.modified_time.cmp(&b.modified_time) }),
+                Order::Taken => self.picture_entries.sort_by_key(|entry| entry.taken_time()),
+                Order::Distance => self.picture_entries.sort_by(|a, b| distance(a).total_cmp(&distance(b))),
                 Order::Name => self.picture_entries.sort_by(|a, b| { a.original_file_path().cmp(&b.original_file_path()) }),
                 Order::Size => self.picture_entries.sort_by(|a, b| { a.file_size.cmp(&b.file_size)} ),
+                Order::Pixels => self.picture_entries.sort_by_key(|entry| entry.pixels()),
+                Order::Aspect => self.picture_entries.sort_by(|a, b| a.aspect_ratio().total_cmp(&b.aspect_ratio())),
                 Order::Value => self.picture_entries.sort_by(|a, b|  { a.cmp_rank(b) }),
                 Order::Label => self.picture_entries.sort_by(|a, b| { a.cmp_label(b) }),
                 Order::Palette => self.picture_entries.sort_by(|a, b| { a.image_data.palette.cmp(&b.image_data.palette) }),
                 Order::Random => self.picture_entries.shuffle(&mut thread_rng()),
+                Order::Sequence => self.picture_entries.sort_by_key(|entry| self.sequence.get(&entry.original_file_path()).copied().unwrap_or(usize::MAX)),
             };
             self.order = Some(order);
             if let Some(index) = self.picture_entries.iter().position(|entry| entry.original_file_path() == original_file_path) {
@@ -3,7 +3,7 @@ use crate::prompt::prompt_yes_no;
 use std::fs::File;
 use std::path::Path;
 use std::collections::HashMap;
-use std::env;
+use crate::config::setting;
 use serde::{Deserialize, Serialize};
 use std::fs::read_to_string;
 
@@ -13,6 +13,7 @@ const KEY_MAP_FILE: &str = "./gallshkey.json";
 #[derive(PartialEq, Copy, Clone, Debug, Deserialize, Serialize)]
 pub enum Command {
     AddTag,
+    AddToAlbum,
     Cancel,
     ChooseOrder,
     CopyLabel,
@@ -37,18 +38,28 @@ pub enum Command {
     LabelTag,
     LastPosition,
     Left,
+    MoveLeft,
+    MoveRight,
+    Near,
     Next,
+    NextFrame,
+    NextMatch,
     NextPage,
     NoStar,
+    Note,
     OneStar,
     PasteLabel,
+    PrevMatch,
     PrevPage,
     QuitWithCancel,
     QuitWithConfirm,
     Random,
+    Redo,
     Relabel,
+    RenameLabel,
     Repeat,
     Right,
+    SavedSearch,
     Search,
     SetRange,
     StartPosition,
@@ -57,10 +68,12 @@ pub enum Command {
     ToggleFullSize,
     TogglePageLimit,
     TogglePalette,
+    TogglePlay,
     ToggleSelect,
     ToggleSingleView,
     TwoStars,
     Uncover,
+    Undo,
     UnSelectPage,
     Unlabel,
     UnselectAll,
@@ -72,6 +85,7 @@ pub type Shortcuts = HashMap<String, Command>;
 pub fn default_shortcuts() -> Shortcuts {
     let shortcuts: Shortcuts = HashMap::from([
         (String::from("at"), Command::AddTag),
+        (String::from("B"), Command::AddToAlbum),
         (String::from("ampersand"), Command::LabelTag),
         (String::from("percent"), Command::DeleteTag),
         (String::from("Escape"), Command::Cancel),
@@ -96,24 +110,34 @@ pub fn default_shortcuts() -> Shortcuts {
         (String::from("l"), Command::Label),
         (String::from("slash"), Command::Label),
         (String::from("L"), Command::Relabel),
+        (String::from("T"), Command::RenameLabel),
         (String::from("z"), Command::LastPosition),
         (String::from("Left"), Command::Left),
         (String::from("t"), Command::Left),
+        (String::from("less"), Command::MoveLeft),
+        (String::from("greater"), Command::MoveRight),
+        (String::from("W"), Command::Near),
         (String::from("space"), Command::Next),
+        (String::from("j"), Command::NextFrame),
+        (String::from("N"), Command::NextMatch),
         (String::from("n"), Command::NextPage),
+        (String::from("colon"), Command::Note),
         (String::from("0"), Command::NoStar),
         (String::from("dollar"), Command::NoStar),
         (String::from("1"), Command::OneStar),
         (String::from("quotedbl"), Command::OneStar),
         (String::from("plus"), Command::PasteLabel),
+        (String::from("P"), Command::PrevMatch),
         (String::from("p"), Command::PrevPage),
         (String::from("q"), Command::QuitWithCancel),
         (String::from("Q"), Command::QuitWithConfirm),
         (String::from("R"), Command::Random),
+        (String::from("y"), Command::Redo),
         (String::from("tab"), Command::Repeat),
         (String::from("Right"), Command::Right),
         (String::from("r"), Command::Right),
         (String::from("S"), Command::Search),
+        (String::from("F"), Command::SavedSearch),
         (String::from("Return"), Command::SetRange),
         (String::from("A"), Command::StartPosition),
         (String::from("3"), Command::ThreeStars),
@@ -122,11 +146,13 @@ pub fn default_shortcuts() -> Shortcuts {
         (String::from("f"), Command::ToggleFullSize),
         (String::from("o"), Command::TogglePageLimit),
         (String::from("x"), Command::TogglePalette),
+        (String::from("k"), Command::TogglePlay),
         (String::from("comma"), Command::ToggleSelect),
         (String::from("period"), Command::ToggleSingleView),
         (String::from("2"), Command::TwoStars),
         (String::from("guillemotleft"), Command::TwoStars),
         (String::from("dollar"), Command::Uncover),
+        (String::from("BackSpace"), Command::Undo),
         (String::from("u"), Command::UnSelectPage),
         (String::from("minus"), Command::Unlabel),
         (String::from("U"), Command::UnselectAll),
@@ -137,7 +163,7 @@ pub fn default_shortcuts() -> Shortcuts {
 }
 
 pub fn load_shortcuts() -> Result<Shortcuts> {
-    if let Ok(key_file_name) = &env::var(KEY_CMD_FILE_VAR) { 
+    if let Some(key_file_name) = &setting(KEY_CMD_FILE_VAR, |settings| settings.key_map.clone()) {
         match read_to_string(key_file_name) {
             Ok(content) => match serde_json::from_str(&content) {
                     Err(err) => Err(anyhow!(err)),
@@ -155,13 +181,13 @@ pub fn load_shortcuts() -> Result<Shortcuts> {
                             Err(err) => Err(anyhow!(err)),
                         }
                     },
-                    Ok(_) => Err(anyhow!("variable GALLSHKEY is not defined. Maybe it should be defined to ~/.gallshkey.json")),
+                    Ok(_) => Err(anyhow!("variable GALLSHKEY is not defined and the library has no key map. Maybe it should be defined to ~/.gallshkey.json")),
                     Err(err) => Err(anyhow!(err)),
                 }
             }
         }
     } else {
-        Err(anyhow!("variable GALLSHKEY is not defined. Maybe it should be defined to ~/.gallshkey.json"))
+        Err(anyhow!("variable GALLSHKEY is not defined and the library has no key map. Maybe it should be defined to ~/.gallshkey.json"))
     }
 }
 
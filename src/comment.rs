@@ -8,6 +8,8 @@ pub enum Comment {
     Label { label: String },
     /// Remove the label on a picture entry
     Unlabel,
+    /// Write a free text note on a picture entry, an empty note removing it
+    Note { note: String },
     /// Add a tag on a picture entry. A given tag can only appear once.
     AddTag { label: String },
     /// Delete the given tag on the picture entry.
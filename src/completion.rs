@@ -1,12 +1,18 @@
 use std::collections::HashSet;
 
+/// the labels starting with prefix, completed up to the end of the next path segment, e.g. place/
+/// for the prefix pl and the label place/france/paris
 pub fn candidates(prefix: &String, labels: &HashSet<String>) -> Vec<String> {
     let mut result: Vec<String> = labels
         .iter()
         .filter(|label| label.starts_with(prefix))
-        .cloned()
+        .map(|label| match label[prefix.len()..].find('/') {
+            Some(position) => label[..prefix.len() + position + 1].to_string(),
+            None => label.clone(),
+        })
         .collect::<Vec<String>>();
     result.sort();
+    result.dedup();
     result
 
 }
@@ -27,4 +33,15 @@ mod tests {
         let expected_fact:Vec<String> = vec!["factum".into()];
         assert_eq!(expected_fact, candidates(&"fact".into(), &labels));
     }
+
+    #[test]
+    fn candidates_are_completed_one_path_segment_at_a_time() {
+        let labels: HashSet<String> = HashSet::from(["place".into(), "place/france/paris".into(), "place/france/lyon".into(), "place/usa".into()]);
+        let expected_pl: Vec<String> = vec!["place".into(), "place/".into()];
+        assert_eq!(expected_pl, candidates(&"pl".into(), &labels));
+        let expected_place: Vec<String> = vec!["place/france/".into(), "place/usa".into()];
+        assert_eq!(expected_place, candidates(&"place/".into(), &labels));
+        let expected_france: Vec<String> = vec!["place/france/lyon".into(), "place/france/paris".into()];
+        assert_eq!(expected_france, candidates(&"place/france/".into(), &labels));
+    }
 }
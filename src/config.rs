@@ -0,0 +1,145 @@
+use anyhow::{anyhow, Result};
+use dirs::config_dir;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs::read_to_string;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use crate::path::replace_tilde_with_home;
+
+const CONFIG_FILE_VAR: &str = "GALLSHCONFIG";
+
+/// settings of a library of pictures; the environment variables override them
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct LibrarySettings {
+    /// database path (GALLSHDB)
+    pub database: Option<String>,
+    /// root directory of the pictures (GALLSHDIR)
+    pub directory: Option<String>,
+    /// directory pictures are copied to (GALLSHTMP)
+    pub temp_directory: Option<String>,
+    /// file the names of extracted pictures are appended to
+    pub extract_file: Option<String>,
+    /// key map file (GALLSHKEY)
+    pub key_map: Option<String>,
+}
+
+/// content of the config file, e.g.
+///
+/// default = "personal"
+///
+/// [libraries.personal]
+/// database = "~/gallsh.db"
+/// directory = "~/Pictures"
+///
+/// [libraries.work]
+/// database = "~/work/gallsh.db"
+/// directory = "~/work/pictures"
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub default: Option<String>,
+    #[serde(default)]
+    pub libraries: HashMap<String, LibrarySettings>,
+}
+
+static SETTINGS: OnceLock<LibrarySettings> = OnceLock::new();
+
+/// path of the config file: $GALLSHCONFIG, or gsr/config.toml in the user config directory
+pub fn config_file_path() -> Option<PathBuf> {
+    match env::var(CONFIG_FILE_VAR) {
+        Ok(file_path) => Some(PathBuf::from(replace_tilde_with_home(&file_path))),
+        Err(_) => config_dir().map(|dir| dir.join("gsr").join("config.toml")),
+    }
+}
+
+impl Config {
+    pub fn parse(content: &str) -> Result<Config> {
+        match toml::from_str(content) {
+            Ok(config) => Ok(config),
+            Err(err) => Err(anyhow!(format!("config file: {}", err))),
+        }
+    }
+
+    /// the settings of the library name, or of the default library if no name is given
+    pub fn library(&self, name: Option<&str>) -> Result<LibrarySettings> {
+        match name.or(self.default.as_deref()) {
+            Some(name) => match self.libraries.get(name) {
+                Some(settings) => Ok(LibrarySettings {
+                    database: settings.database.as_deref().map(replace_tilde_with_home),
+                    directory: settings.directory.as_deref().map(replace_tilde_with_home),
+                    temp_directory: settings.temp_directory.as_deref().map(replace_tilde_with_home),
+                    extract_file: settings.extract_file.as_deref().map(replace_tilde_with_home),
+                    key_map: settings.key_map.as_deref().map(replace_tilde_with_home),
+                }),
+                None => Err(anyhow!(format!("library {} is not defined in the config file", name))),
+            },
+            None => Ok(LibrarySettings::default()),
+        }
+    }
+}
+
+/// read the config file and select the library name, or the default library; must be called once,
+/// before the settings are used. Without a config file, only the environment variables are used.
+pub fn select_library(name: Option<&str>) -> Result<()> {
+    let config = match config_file_path() {
+        Some(path) if path.exists() => match read_to_string(&path) {
+            Ok(content) => Config::parse(&content)?,
+            Err(err) => return Err(anyhow!(format!("can't read config file {}: {}", path.display(), err))),
+        },
+        _ => Config::default(),
+    };
+    let settings = config.library(name)?;
+    match SETTINGS.set(settings) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(anyhow!("library already selected")),
+    }
+}
+
+/// the settings of the selected library
+pub fn settings() -> LibrarySettings {
+    SETTINGS.get().cloned().unwrap_or_default()
+}
+
+/// the value of the environment variable var_name if defined, else the setting of the selected library
+pub fn setting(var_name: &str, setting: fn(&LibrarySettings) -> Option<String>) -> Option<String> {
+    match env::var(var_name) {
+        Ok(value) => Some(value),
+        Err(_) => setting(&settings()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "
+        default = \"personal\"
+
+        [libraries.personal]
+        database = \"/home/me/gallsh.db\"
+        directory = \"/home/me/Pictures\"
+
+        [libraries.work]
+        database = \"/work/gallsh.db\"
+        key_map = \"/work/gallshkey.json\"
+    ";
+
+    #[test]
+    fn libraries_are_selected_by_name_or_by_default() {
+        let config = Config::parse(CONFIG).unwrap();
+        assert_eq!(Some(String::from("/home/me/Pictures")), config.library(None).unwrap().directory);
+        let work = config.library(Some("work")).unwrap();
+        assert_eq!(Some(String::from("/work/gallsh.db")), work.database);
+        assert_eq!(None, work.directory);
+        assert!(config.library(Some("holidays")).is_err());
+        assert_eq!(LibrarySettings::default(), Config::default().library(None).unwrap());
+    }
+
+    #[test]
+    fn unknown_settings_are_reported() {
+        assert!(Config::parse("[libraries.work]\ndatabse = \"/work/gallsh.db\"").is_err());
+    }
+}
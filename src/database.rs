@@ -1,6 +1,12 @@
+use crate::config::setting;
+use crate::exif_data::ExifData;
+use crate::fsck::{issues, Issue, PALETTE_BLOB_SIZE, StoredPicture, Table};
+use crate::geo::distance_km;
+use crate::history::HistoryEntry;
 use crate::image_data::ImageData;
+use crate::library::{CoverRecord, ImportReport, Library, PictureRecord, TagRecord};
 use anyhow::{anyhow, Result};
-use crate::palette::{palette_to_blob,blob_to_palette};
+use crate::palette::{palette_to_blob,blob_to_palette,Palette};
 use crate::path::file_name;
 use crate::path::replace_tilde_with_home;
 use crate::path::replace_home_with_tilde;
@@ -8,19 +14,41 @@ use crate::path::get_picture_file_paths;
 use crate::path::{is_prefix_path, standard_directory,file_path_directory};
 use crate::picture_entry::make_picture_entry;
 use crate::picture_entry::{PictureEntry, PictureEntries};
+use crate::picture_io::{content_hash, get_palette_from_picture, move_sidecar_files, read_dimensions, read_file_info, read_frame_count};
 use crate::prompt::prompt_yes_no;
+use crate::query::Query;
 use crate::rank::Rank;
+use crate::schema::{MIGRATIONS, schema_version};
+use crate::search::Filters;
 use rusqlite::{Row, Error};
-use rusqlite::{params, Connection};
+use rusqlite::functions::FunctionFlags;
+use rusqlite::{params, params_from_iter, Connection, TransactionBehavior};
+use rusqlite::types::Value;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::env;
 use std::path::{Path,PathBuf};
 use std::time::Duration;
-use std::time::UNIX_EPOCH;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 const DATABASE_CONNECTION: &str = "GALLSHDB";
 
+// fill the full text index again from the Picture and Tag tables
+const SEARCH_INDEX_REBUILD: &str =
+    "DELETE FROM Picture_Search;                                 \n\
+    INSERT INTO Picture_Search (File_Path, Path, Label, Tags, Note) \n\
+        SELECT File_Path, File_Path, COALESCE(Label, ''),       \n\
+            COALESCE((SELECT group_concat(Tag.Label, ' ') FROM Tag WHERE Tag.File_Path = Picture.File_Path), ''), COALESCE(Note, '') \n\
+        FROM Picture;";
+
+// how long to wait for another gsr process to release its lock on the database before failing
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// columns read by rusqlite_to_picture_entry, in that order
+const PICTURE_COLUMNS: &str = "File_Path, File_Size, Colors, Modified_Time, Rank, Palette, Label, Selected, Deleted, Cover, Content_Hash, Perceptual_Hash, Note, \
+    Taken_Time, Camera_Make, Camera_Model, Lens, Exposure, ISO, Focal_Length, Orientation, \
+    Latitude, Longitude, Altitude, Width, Height, Frames";
+
 #[derive(Debug)]
 pub struct Database {
     connection: Connection,
@@ -28,21 +56,34 @@ pub struct Database {
 
 impl Database {
 
-    /// initialize the database, creating the schema if needed.
-    pub fn initialize(create_schema: bool) -> Result<Self> {
-        match env::var(DATABASE_CONNECTION) {
-            Ok(connection_string) => match Self::from_path(&connection_string) {
-                Ok(database) => {
-                    if create_schema {
-                        match database.rusqlite_create_schema() {
-                            Ok(()) => {},
-                            Err(err) => return Err(anyhow!(err)),
-                        }
-                    };
-                    Ok(database)
+    /// initialize the database, migrating the schema to the current version if needed.
+    pub fn initialize() -> Result<Self> {
+        match setting(DATABASE_CONNECTION, |settings| settings.database.clone()) {
+            Some(connection_string) => match Self::from_path(&connection_string) {
+                Ok(mut database) => match database.migrate() {
+                    Ok(()) => Ok(database),
+                    Err(err) => Err(anyhow!(err)),
                 },
                 Err(err) => Err(anyhow!(err)),
             },
+            None => Err(anyhow!("variable GALLSHDB is not defined and the library has no database")),
+        }
+    }
+
+    /// apply the migrations not yet applied to the database, refusing a database newer than this program
+    pub fn migrate(&mut self) -> Result<()> {
+        match self.rusqlite_schema_version() {
+            Ok(version) if version > schema_version() => Err(anyhow!(format!("database schema version {} is newer than the version {} supported by this program", version, schema_version()))),
+            Ok(version) => {
+                for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+                    println!("migrating database schema to version {}", index + 1);
+                    match self.rusqlite_apply_migration(index + 1, migration) {
+                        Ok(()) => {},
+                        Err(err) => return Err(anyhow!(format!("migration to schema version {} failed: {}", index + 1, err))),
+                    }
+                };
+                Ok(())
+            },
             Err(err) => Err(anyhow!(err)),
         }
     }
@@ -79,28 +120,50 @@ impl Database {
         }
     }
     
-    /// create the database from the given connection_string.
+    /// create the database from the given connection_string, in WAL journal mode so that readers
+    /// don't block the writer, and waiting for the locks of other processes instead of failing
     fn from_path(connection_string: &str) -> Result<Self> {
-        match Connection::open(connection_string) {
+        let result = Connection::open(connection_string)
+            .and_then(|connection| connection.busy_timeout(BUSY_TIMEOUT).map(|_| connection))
+            .and_then(|connection| {
+                connection.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<usize, String>(0))
+                    .map(|_| connection)
+            })
+            .and_then(|connection| add_distance_function(&connection).map(|_| connection));
+        match result {
             Ok(connection) => Ok(Database { connection }),
             Err(err) => Err(anyhow!(err)),
         }
     }
 
+    /// start a transaction grouping the updates until end_transaction
+    pub fn begin_transaction(&self) -> Result<()> {
+        match self.connection.execute_batch("BEGIN IMMEDIATE;") {
+            Ok(()) => Ok(()),
+            Err(err) => Err(anyhow!(err)),
+        }
+    }
+
+    /// commit the updates since begin_transaction if result is ok, roll them back otherwise
+    pub fn end_transaction<T>(&self, result: Result<T>) -> Result<T> {
+        match result {
+            Ok(value) => match self.connection.execute_batch("COMMIT;") {
+                Ok(()) => Ok(value),
+                Err(err) => Err(anyhow!(err)),
+            },
+            Err(err) => {
+                let _ = self.connection.execute_batch("ROLLBACK;");
+                Err(err)
+            },
+        }
+    }
+
     /// selects all the pictures entries used as cover for a directory
     fn rusqlite_select_cover_picture_entries(&mut self) -> Result<PictureEntries, Error> {
-        self.connection.prepare(
-            "SELECT File_Path,            \n\
-            File_Size,                    \n\
-            Colors,                       \n\
-            Modified_Time,                \n\
-            Rank, Palette,                \n\
-            Label,                        \n\
-            Selected,                     \n\
-            Deleted,                      \n\
-            Cover                         \n\
+        self.connection.prepare(&format!(
+            "SELECT {}                    \n\
             FROM Picture                  \n\
-            WHERE Cover = True;")
+            WHERE Cover = True;", PICTURE_COLUMNS))
             .and_then(|mut statement| {
                 statement.query([])
                     .and_then(|mut rows| {
@@ -118,39 +181,33 @@ impl Database {
             })
     }
 
-    /// create the database schema
-    fn rusqlite_create_schema(&self) -> Result<(),Error> {
-        println!("creating database schema");
+    /// read the schema version of the database, 0 for a database never migrated
+    fn rusqlite_schema_version(&self) -> Result<usize,Error> {
         self.connection.execute(
-            "CREATE TABLE IF NOT EXISTS Picture (    \n\
-                File_Path TEXT NOT NULL PRIMARY KEY, \n\
-                File_Size INTEGER,                   \n\
-                Colors INTEGER,                      \n\
-                Modified_Time INTEGER,               \n\
-                Rank INTEGER,                        \n\
-                Palette BLOB,                        \n\
-                Label TEXT,                          \n\
-                Selected BOOLEAN,                    \n\
-                Deleted BOOLEAN,                     \n\
-                Cover BOOLEAN);", [])
+            "CREATE TABLE IF NOT EXISTS Schema_Version ( \n\
+             Version INTEGER NOT NULL PRIMARY KEY,       \n\
+             Applied_Time INTEGER);", [])
             .and_then(|_| {
-                self.connection.execute(
-                    "CREATE TABLE IF NOT EXISTS Tag ( \n\
-                    File_Path TEXT NOT NULL,          \n\
-                    Label TEXT NOT NULL,              \n\
-                    PRIMARY KEY ( File_Path, Label));", [])
-                    .and_then(|_| {
-                        self.connection.execute(
-                        "CREATE TABLE IF NOT EXISTS Cover (  \n\
-                         Dir_Path TEXT NOT NULL,             \n\
-                         File_Name TEXT NOT NULL,            \n\
-                         Rank INTEGER,                       \n\
-                         PRIMARY KEY (Dir_Path, File_Name));", [])
-                            .map(|_|  ())
-                    })
+                self.connection.query_row(
+                    "SELECT COALESCE(MAX(Version), 0) FROM Schema_Version;", [],
+                    |row| row.get::<usize, i64>(0))
+                    .map(|version| version as usize)
             })
     }
 
+    /// apply a migration and record its version in a single transaction
+    fn rusqlite_apply_migration(&mut self, version: usize, migration: &str) -> Result<(),Error> {
+        let applied_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        let transaction = self.connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        transaction.execute_batch(migration)?;
+        transaction.execute(
+            "INSERT INTO Schema_Version   \n\
+             (Version, Applied_Time)      \n\
+             VALUES (?1, ?2);",
+             params![version as i64, applied_time])?;
+        transaction.commit()
+    }
+
     fn populate(&self, difference_opt: Option<HashSet<&String>>) -> Result<PictureEntries> {
         let mut count: usize = 0;
         let total = match difference_opt {
@@ -160,7 +217,7 @@ impl Database {
         if total > 0 {
             let mut picture_entries: PictureEntries = vec![];
             for file_path in difference_opt.unwrap() {
-                match self.insert_picture_entry(file_path) {
+                match Self::picture_entry_from_file(file_path) {
                     Ok(picture_entry) => {
                         picture_entries.push(picture_entry)
                     },
@@ -169,7 +226,12 @@ impl Database {
                 count += 1;
                 println!("{}/{}", count, total);
             };
-            Ok(picture_entries)
+            // the pictures are read before the transaction, so that the database is locked only while inserting
+            self.begin_transaction()?;
+            let result = picture_entries.iter()
+                .try_for_each(|picture_entry| self.rusqlite_insert_picture_entry(picture_entry.clone()).map(|_| ()))
+                .map_err(|err| anyhow!(err));
+            self.end_transaction(result).map(|_| picture_entries)
         } else {
             Ok(vec![])
         }
@@ -223,8 +285,11 @@ impl Database {
              Label = ?6,                \n\
              Selected = ?7,             \n\
              Deleted = ?8,              \n\
-             Cover = ?9
-             WHERE File_Path = ?10;",
+             Cover = ?9,                \n\
+             Perceptual_Hash = ?10,     \n\
+             Updated_Time = ?11,        \n\
+             Note = ?12                 \n\
+             WHERE File_Path = ?13;",
              params![
              entry.file_size as i64,
              entry.image_data.colors as i64,
@@ -235,6 +300,9 @@ impl Database {
              entry.image_data.selected as i64,
              entry.deleted as i64,
              entry.image_data.cover,
+             entry.image_data.perceptual_hash.map(|hash| hash as i64),
+             SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64,
+             entry.image_data.note,
              &replace_home_with_tilde(&*entry.file_path)])
                  .and_then(|_| {
                      self.rusqlite_delete_tags_for_file_path(&entry.file_path)
@@ -259,7 +327,7 @@ impl Database {
     }
 
     fn rusqlite_to_picture_entry(row: &Row) -> Result<PictureEntry,Error> {
-        Ok(make_picture_entry(
+        let mut picture_entry = make_picture_entry(
                 { let file_path:String =row.get(0)?;
                     replace_tilde_with_home(&file_path)
                 },
@@ -277,7 +345,8 @@ impl Database {
                     palette: {
                         let blob: Vec<u8> = row.get(5)?;
                         let mut bytes: [u8;36] = [0;36];
-                        bytes.copy_from_slice(&blob[..36]);
+                        let size = blob.len().min(PALETTE_BLOB_SIZE);
+                        bytes[..size].copy_from_slice(&blob[..size]);
                         blob_to_palette(&bytes)
                     },
                     label: {
@@ -297,12 +366,35 @@ impl Database {
                         result
                     },
                     tags: HashSet::new(),
+                    note: row.get::<usize, Option<String>>(12)?.unwrap_or_default(),
+                    perceptual_hash: {
+                        let hash: Option<i64> = row.get(11)?;
+                        hash.map(|h| h as u64)
+                    },
                 },
                 {
                     let result:bool = row.get(8)?;
                     result
                 },
-                ))
+                );
+        picture_entry.content_hash = row.get(10)?;
+        picture_entry.exif = ExifData {
+            taken: row.get(13)?,
+            make: row.get(14)?,
+            model: row.get(15)?,
+            lens: row.get(16)?,
+            exposure: row.get(17)?,
+            iso: row.get(18)?,
+            focal_length: row.get(19)?,
+            orientation: row.get(20)?,
+            latitude: row.get(21)?,
+            longitude: row.get(22)?,
+            altitude: row.get(23)?,
+        };
+        picture_entry.width = row.get(24)?;
+        picture_entry.height = row.get(25)?;
+        picture_entry.frames = row.get(26)?;
+        Ok(picture_entry)
     }
 
     fn rusqlite_select_all_picture_file_paths(&self) -> Result<HashSet<String>,Error> {
@@ -319,6 +411,22 @@ impl Database {
             })
     }
 
+    /// time of the last update of each picture, if updated since the database has update times
+    pub fn select_updated_times(&self) -> Result<HashMap<String,Option<i64>>> {
+        let result = self.connection.prepare("SELECT File_Path, Updated_Time FROM Picture;")
+            .and_then(|mut statement| {
+                statement.query_map([], |row| {
+                    let file_path: String = row.get(0)?;
+                    Ok((replace_tilde_with_home(&file_path), row.get::<usize, Option<i64>>(1)?))
+                })
+                .and_then(|rows| rows.collect::<Result<HashMap<String,Option<i64>>,Error>>())
+            });
+        match result {
+            Ok(updated_times) => Ok(updated_times),
+            Err(err) => Err(anyhow!(err)),
+        }
+    }
+
     pub fn select_all_picture_file_paths(&self) -> Result<HashSet<String>> {
         match self.rusqlite_select_all_picture_file_paths() {
             Ok(result) => Ok(result),
@@ -336,6 +444,12 @@ impl Database {
                      WHERE File_Path = ?1;", params![replace_home_with_tilde(file_path)])
                     .map(|_| ())
             })
+            .and_then(|_| {
+                self.connection.execute(
+                    "DELETE FROM Album \n\
+                     WHERE File_Path = ?1;", params![replace_home_with_tilde(file_path)])
+                    .map(|_| ())
+            })
     }
 
     pub fn delete_picture(&self, file_path: &str) -> Result<()> {
@@ -374,23 +488,14 @@ impl Database {
         }
     }
 
-    fn rusqlite_select_pictures(&self, query: &str) -> Result<PictureEntries, Error> {
-        let full_query: String =
-            "SELECT File_Path,     \n\
-              File_Size,             \n\
-              Colors,                \n\
-              Modified_Time,         \n\
-              Rank,                  \n\
-              Palette,               \n\
-              Label,                 \n\
-              Selected,              \n\
-              Deleted,               \n\
-              Cover                  \n\
+    fn rusqlite_select_pictures(&self, condition: &str, parameters: &[Value]) -> Result<PictureEntries, Error> {
+        let full_query: String = format!(
+            "SELECT {}             \n\
               FROM Picture           \n\
-              WHERE ".to_owned() + query + ";";
+              WHERE {};", PICTURE_COLUMNS, condition);
         self.connection.prepare(&full_query)
             .and_then(|mut statement| {
-                statement.query([])
+                statement.query(params_from_iter(parameters.iter()))
                     .and_then(|mut rows| {
                             let mut picture_entries: PictureEntries = vec![];
                             while let Some(row) = rows.next()? {
@@ -404,8 +509,11 @@ impl Database {
             })
     }
 
-    pub fn select_pictures(&self, query: &str) -> Result<PictureEntries> {
-        match self.rusqlite_select_pictures(query) {
+    /// select the picture entries matching the query
+    pub fn select_pictures(&self, query: &Query) -> Result<PictureEntries> {
+        let mut parameters: Vec<Value> = vec![];
+        let condition = query.to_sql(&mut parameters);
+        match self.rusqlite_select_pictures(&condition, &parameters) {
             Ok(result) => Ok(result),
             Err(err) => Err(anyhow!(err)),
         }
@@ -510,6 +618,33 @@ pub fn load_directories(&self) -> Result<Vec<(String,usize)>> {
         Err(err) => Err(anyhow!(err)),
     }
 }
+/// the tags of all the pictures, in a single scan of the Tag table
+pub fn select_tags_by_file_path(&self) -> Result<HashMap<String,HashSet<String>>> {
+    let mut result: HashMap<String,HashSet<String>> = HashMap::new();
+    let query = "SELECT File_Path, Label FROM Tag;";
+    match self.connection.prepare(query) {
+        Ok(mut statement) => {
+            match statement.query_map([], |row| {
+                Ok((row.get::<usize, String>(0)?, row.get::<usize, String>(1)?))
+            }) {
+                Ok(rows) => {
+                    for row in rows {
+                        match row {
+                            Ok((file_path, label)) => {
+                                result.entry(replace_tilde_with_home(&file_path)).or_default().insert(label);
+                            },
+                            Err(err) => return Err(anyhow!(err)),
+                        }
+                    };
+                    Ok(result)
+                },
+                Err(err) => Err(anyhow!(err)),
+            }
+        },
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
 pub fn entry_tags(&self, file_path: &str) -> Result<HashSet<String>> {
     let mut result: HashSet<String> = HashSet::new();
     let query = "SELECT DISTINCT Label FROM Tag WHERE File_Path = ?1;";
@@ -548,10 +683,28 @@ fn rusqlite_insert_picture_entry(&self, picture_entry: PictureEntry) -> Result<u
      Selected,                    \n\
      Deleted,                     \n\
      Cover,                       \n\
-     Palette)                     \n\
-     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10);",
+     Palette,                     \n\
+     Content_Hash,                \n\
+     Perceptual_Hash,             \n\
+     Note,                        \n\
+     Taken_Time,                  \n\
+     Camera_Make,                 \n\
+     Camera_Model,                \n\
+     Lens,                        \n\
+     Exposure,                    \n\
+     ISO,                         \n\
+     Focal_Length,                \n\
+     Orientation,                 \n\
+     Latitude,                    \n\
+     Longitude,                   \n\
+     Altitude,                    \n\
+     Width,                       \n\
+     Height,                      \n\
+     Frames,                      \n\
+     GPS_Read)                    \n\
+     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28);",
      params![
-     picture_entry.file_path,
+     replace_home_with_tilde(&picture_entry.file_path),
      picture_entry.file_size as i64,
      picture_entry.image_data.colors as i64,
      picture_entry.modified_time.duration_since(UNIX_EPOCH).unwrap().as_secs() as i64,
@@ -560,7 +713,25 @@ fn rusqlite_insert_picture_entry(&self, picture_entry: PictureEntry) -> Result<u
      picture_entry.image_data.selected as i64,
      picture_entry.deleted as i64,
      picture_entry.image_data.cover as i64,
-     palette_to_blob(&picture_entry.image_data.palette)])
+     palette_to_blob(&picture_entry.image_data.palette),
+     picture_entry.content_hash,
+     picture_entry.image_data.perceptual_hash.map(|hash| hash as i64),
+     picture_entry.image_data.note,
+     picture_entry.exif.taken,
+     picture_entry.exif.make,
+     picture_entry.exif.model,
+     picture_entry.exif.lens,
+     picture_entry.exif.exposure,
+     picture_entry.exif.iso,
+     picture_entry.exif.focal_length,
+     picture_entry.exif.orientation,
+     picture_entry.exif.latitude,
+     picture_entry.exif.longitude,
+     picture_entry.exif.altitude,
+     picture_entry.width,
+     picture_entry.height,
+     picture_entry.frames,
+     picture_entry.exif.orientation.is_some()])
 }
 
 pub fn insert_new_picture_entry(&self, picture_entry: PictureEntry) -> Result<()> {
@@ -570,14 +741,908 @@ pub fn insert_new_picture_entry(&self, picture_entry: PictureEntry) -> Result<()
     }
 }
 
-pub fn insert_picture_entry(&self, file_path: &str) -> Result<PictureEntry> {
+// the picture entry of a picture file, with its content hash and EXIF metadata
+fn picture_entry_from_file(file_path: &str) -> Result<PictureEntry> {
     match PictureEntry::from_file(file_path) {
-        Ok(picture_entry) => {
-            match self.rusqlite_insert_picture_entry(picture_entry.clone()) {
-                Ok(_) => Ok(picture_entry),
-                Err(err) => Err(anyhow!(err)),
+        Ok(mut picture_entry) => match content_hash(file_path) {
+            Ok(hash) => {
+                picture_entry.content_hash = Some(hash);
+                picture_entry.exif = ExifData::from_file(file_path);
+                Ok(picture_entry)
+            },
+            Err(err) => Err(anyhow!(err)),
+        },
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+pub fn insert_picture_entry(&self, file_path: &str) -> Result<PictureEntry> {
+    match Self::picture_entry_from_file(file_path) {
+        Ok(picture_entry) => match self.rusqlite_insert_picture_entry(picture_entry.clone()) {
+            Ok(_) => Ok(picture_entry),
+            Err(err) => Err(anyhow!(err)),
+        },
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+fn rusqlite_select_file_paths_without_content_hash(&self) -> Result<Vec<String>,Error> {
+    self.connection.prepare("SELECT File_Path FROM Picture WHERE Content_Hash IS NULL;")
+        .and_then(|mut statement| {
+            statement.query_map([], |row| row.get::<usize, String>(0))
+                .and_then(|rows| rows.collect())
+        })
+}
+
+fn rusqlite_update_content_hash(&self, stored_file_path: &str, hash: &str) -> Result<(),Error> {
+    self.connection.execute(
+        "UPDATE Picture SET Content_Hash = ?1 WHERE File_Path = ?2;",
+        params![hash, stored_file_path])
+        .map(|_| ())
+}
+
+fn rusqlite_select_file_paths_without_exif(&self) -> Result<Vec<String>,Error> {
+    self.connection.prepare("SELECT File_Path FROM Picture WHERE Orientation IS NULL OR (Latitude IS NULL AND GPS_Read = 0);")
+        .and_then(|mut statement| {
+            statement.query_map([], |row| row.get::<usize, String>(0))
+                .and_then(|rows| rows.collect())
+        })
+}
+
+fn rusqlite_update_exif(&self, stored_file_path: &str, exif: &ExifData) -> Result<(),Error> {
+    self.connection.execute(
+        "UPDATE Picture SET Taken_Time = ?1, Camera_Make = ?2, Camera_Model = ?3, Lens = ?4, \n\
+         Exposure = ?5, ISO = ?6, Focal_Length = ?7, Orientation = ?8, \n\
+         Latitude = ?9, Longitude = ?10, Altitude = ?11, GPS_Read = 1 WHERE File_Path = ?12;",
+        params![exif.taken, exif.make, exif.model, exif.lens, exif.exposure, exif.iso, exif.focal_length, exif.orientation,
+            exif.latitude, exif.longitude, exif.altitude, stored_file_path])
+        .map(|_| ())
+}
+
+/// read the EXIF metadata of the pictures that were stored before it was read at insert time,
+/// returning the number of pictures read
+pub fn read_missing_exif(&self) -> Result<usize> {
+    match self.rusqlite_select_file_paths_without_exif() {
+        Ok(stored_file_paths) => {
+            // the files are read before the transaction, so that the database is locked only while updating
+            let exifs: Vec<(String, ExifData)> = stored_file_paths.into_iter()
+                .filter_map(|stored_file_path| {
+                    let file_path = replace_tilde_with_home(&stored_file_path);
+                    if Path::new(&file_path).exists() {
+                        Some((stored_file_path, ExifData::from_file(&file_path)))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            self.begin_transaction()?;
+            let result = exifs.iter()
+                .try_for_each(|(stored_file_path, exif)| self.rusqlite_update_exif(stored_file_path, exif))
+                .map_err(|err| anyhow!(err));
+            self.end_transaction(result).map(|_| exifs.len())
+        },
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+fn rusqlite_select_file_paths_without_dimensions(&self) -> Result<Vec<String>,Error> {
+    self.connection.prepare("SELECT File_Path FROM Picture WHERE Width IS NULL OR Height IS NULL OR Frames IS NULL;")
+        .and_then(|mut statement| {
+            statement.query_map([], |row| row.get::<usize, String>(0))
+                .and_then(|rows| rows.collect())
+        })
+}
+
+fn rusqlite_update_dimensions(&self, stored_file_path: &str, width: u32, height: u32, frames: u32) -> Result<(),Error> {
+    self.connection.execute(
+        "UPDATE Picture SET Width = ?1, Height = ?2, Frames = ?3 WHERE File_Path = ?4;",
+        params![width, height, frames, stored_file_path])
+        .map(|_| ())
+}
+
+/// read the width, height and number of frames of the pictures that were stored before they were read at insert time,
+/// returning the number of pictures read
+pub fn read_missing_dimensions(&self) -> Result<usize> {
+    match self.rusqlite_select_file_paths_without_dimensions() {
+        Ok(stored_file_paths) => {
+            // the files are read before the transaction, so that the database is locked only while updating
+            let dimensions: Vec<(String, u32, u32, u32)> = stored_file_paths.into_iter()
+                .filter_map(|stored_file_path| {
+                    let file_path = replace_tilde_with_home(&stored_file_path);
+                    read_dimensions(&file_path).ok()
+                        .map(|(width, height)| (stored_file_path, width, height, read_frame_count(&file_path).unwrap_or(1)))
+                })
+                .collect();
+            self.begin_transaction()?;
+            let result = dimensions.iter()
+                .try_for_each(|(stored_file_path, width, height, frames)| self.rusqlite_update_dimensions(stored_file_path, *width, *height, *frames))
+                .map_err(|err| anyhow!(err));
+            self.end_transaction(result).map(|_| dimensions.len())
+        },
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+/// compute the content hash of the pictures that don't have one yet, returning the number of pictures hashed
+pub fn rehash(&self) -> Result<usize> {
+    match self.rusqlite_select_file_paths_without_content_hash() {
+        Ok(stored_file_paths) => {
+            let total = stored_file_paths.len();
+            // the files are hashed before the transaction, so that the database is locked only while updating
+            let mut hashes: Vec<(String, String)> = vec![];
+            for stored_file_path in stored_file_paths {
+                let file_path = replace_tilde_with_home(&stored_file_path);
+                match content_hash(&file_path) {
+                    Ok(hash) => {
+                        hashes.push((stored_file_path, hash));
+                        println!("{}/{}", hashes.len(), total);
+                    },
+                    Err(err) => eprintln!("{}: {}", file_path, err),
+                }
+            };
+            self.begin_transaction()?;
+            let result = hashes.iter()
+                .try_for_each(|(stored_file_path, hash)| self.rusqlite_update_content_hash(stored_file_path, hash))
+                .map_err(|err| anyhow!(err));
+            self.end_transaction(result).map(|_| hashes.len())
+        },
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+fn rusqlite_select_missing_pictures(&self) -> Result<Vec<(String, u64, Option<String>)>,Error> {
+    self.connection.prepare("SELECT File_Path, File_Size, Content_Hash FROM Picture;")
+        .and_then(|mut statement| {
+            statement.query_map([], |row| {
+                let file_size: i64 = row.get(1)?;
+                Ok((row.get::<usize, String>(0)?, file_size as u64, row.get::<usize, Option<String>>(2)?))
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>,Error>>())
+        })
+        .map(|pictures| pictures.into_iter()
+            .filter(|(stored_file_path, _, _)| !Path::new(&replace_tilde_with_home(stored_file_path)).exists())
+            .collect())
+}
+
+fn rusqlite_move_picture(&mut self, stored_file_path: &str, new_file_path: &str) -> Result<(),Error> {
+    let old_file_path = replace_tilde_with_home(stored_file_path);
+    // keep the form the path was stored with
+    let new_stored_file_path = if stored_file_path.starts_with('~') {
+        replace_home_with_tilde(new_file_path)
+    } else {
+        new_file_path.to_string()
+    };
+    let transaction = self.connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    transaction.execute(
+        "UPDATE Picture SET File_Path = ?1 WHERE File_Path = ?2;",
+        params![new_stored_file_path, stored_file_path])?;
+    transaction.execute(
+        "UPDATE Tag SET File_Path = ?1 WHERE File_Path = ?2;",
+        params![replace_home_with_tilde(new_file_path), replace_home_with_tilde(&old_file_path)])?;
+    transaction.execute(
+        "UPDATE Album SET File_Path = ?1 WHERE File_Path = ?2;",
+        params![replace_home_with_tilde(new_file_path), replace_home_with_tilde(&old_file_path)])?;
+    transaction.execute(
+        "UPDATE Cover SET Dir_Path = ?1, File_Name = ?2 WHERE Dir_Path = ?3 AND File_Name = ?4;",
+        params![
+            replace_home_with_tilde(&file_path_directory(new_file_path)),
+            file_name(new_file_path),
+            replace_home_with_tilde(&file_path_directory(&old_file_path)),
+            file_name(&old_file_path)])?;
+    transaction.commit()
+}
+
+/// rewrite the database path of a picture that was moved outside gsr, and move its thumbnail and image data files
+pub fn move_picture(&mut self, stored_file_path: &str, new_file_path: &str) -> Result<()> {
+    match self.rusqlite_move_picture(stored_file_path, new_file_path) {
+        Ok(()) => move_sidecar_files(&replace_tilde_with_home(stored_file_path), new_file_path),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+/// pairs of (stored path, new path) matching pictures missing from their database path
+/// with pictures in directory that are not in the database, by file size and content hash
+pub fn reconcile_matches(&self, directory: &str) -> Result<Vec<(String,String)>> {
+    let missing = match self.rusqlite_select_missing_pictures() {
+        Ok(missing) => missing,
+        Err(err) => return Err(anyhow!(err)),
+    };
+    let unhashed = missing.iter().filter(|(_, _, hash)| hash.is_none()).count();
+    if unhashed > 0 {
+        println!("{} missing pictures have no content hash and can't be matched (use --rehash before moving files)", unhashed);
+    };
+    let mut candidates: HashMap<(u64,String), Vec<String>> = HashMap::new();
+    for (stored_file_path, file_size, hash) in missing {
+        if let Some(hash) = hash {
+            candidates.entry((file_size, hash)).or_default().push(stored_file_path)
+        }
+    };
+    let sizes: HashSet<u64> = candidates.keys().map(|(file_size, _)| *file_size).collect();
+    let database_set = match self.select_all_picture_file_paths() {
+        Ok(database_set) => database_set,
+        Err(err) => return Err(anyhow!(err)),
+    };
+    let file_paths = match get_picture_file_paths(directory) {
+        Ok(file_paths) => file_paths,
+        Err(err) => return Err(anyhow!(err)),
+    };
+    let mut matches: Vec<(String,String)> = vec![];
+    for file_path in file_paths.iter().filter(|file_path| !database_set.contains(*file_path)) {
+        let file_size = match read_file_info(file_path) {
+            Ok((file_size, _)) => file_size,
+            Err(err) => return Err(err),
+        };
+        if sizes.contains(&file_size) {
+            let hash = match content_hash(file_path) {
+                Ok(hash) => hash,
+                Err(err) => return Err(anyhow!(err)),
+            };
+            if let Some(stored_file_path) = candidates.get_mut(&(file_size, hash)).and_then(|stored_file_paths| stored_file_paths.pop()) {
+                matches.push((stored_file_path, file_path.to_string()))
             }
+        }
+    };
+    Ok(matches)
+}
+
+/// match pictures missing from their database path with new picture files in directory and
+/// move their data after confirmation, returning the number of pictures moved
+pub fn reconcile(&mut self, directory: &str) -> Result<usize> {
+    let matches = match self.reconcile_matches(directory) {
+        Ok(matches) => matches,
+        Err(err) => return Err(anyhow!(err)),
+    };
+    if matches.is_empty() {
+        println!("no moved picture found");
+        return Ok(0)
+    };
+    for (stored_file_path, file_path) in &matches {
+        println!("{} → {}", stored_file_path, file_path)
+    };
+    match prompt_yes_no(&format!("move the data of these {} pictures to their new path ?", matches.len())) {
+        Ok(Some('y')) | Ok(Some('Y')) => {
+            for (stored_file_path, file_path) in &matches {
+                if let Err(err) = self.move_picture(stored_file_path, file_path) {
+                    return Err(anyhow!(err))
+                }
+            };
+            Ok(matches.len())
+        },
+        Ok(_) => Ok(0),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+fn rusqlite_insert_history_batch(&mut self, history: &[HistoryEntry]) -> Result<(),Error> {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+    // a savepoint rather than a transaction, as the batch can be part of a transaction of the catalog
+    let transaction = self.connection.savepoint()?;
+    // a new batch makes the undone batches impossible to redo
+    transaction.execute("DELETE FROM History WHERE Undone = TRUE;", [])?;
+    let batch: i64 = transaction.query_row("SELECT COALESCE(MAX(Batch), 0) + 1 FROM History;", [], |row| row.get(0))?;
+    for entry in history {
+        transaction.execute(
+            "INSERT INTO History                                          \n\
+             (Batch, File_Path, Comment, Before, After, Deleted_Before, Deleted_After, Time) \n\
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8);",
+            params![
+            batch,
+            replace_home_with_tilde(&entry.file_path),
+            entry.comment,
+            serde_json::to_string(&entry.before).unwrap_or_default(),
+            serde_json::to_string(&entry.after).unwrap_or_default(),
+            entry.deleted_before,
+            entry.deleted_after,
+            time])?;
+    };
+    transaction.commit()
+}
+
+/// record comments applied at once as a batch that can be undone
+pub fn insert_history_batch(&mut self, history: &[HistoryEntry]) -> Result<()> {
+    if history.is_empty() {
+        return Ok(())
+    };
+    match self.rusqlite_insert_history_batch(history) {
+        Ok(()) => Ok(()),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+fn rusqlite_select_history_batch(&self, undo: bool) -> Result<Option<(i64, Vec<HistoryEntry>)>,Error> {
+    // undo the last batch not undone yet, redo the first batch undone
+    let batch_query = if undo {
+        "SELECT MAX(Batch) FROM History WHERE Undone = FALSE;"
+    } else {
+        "SELECT MIN(Batch) FROM History WHERE Undone = TRUE;"
+    };
+    let batch: Option<i64> = self.connection.query_row(batch_query, [], |row| row.get(0))?;
+    match batch {
+        None => Ok(None),
+        Some(batch) => self.connection.prepare(
+            "SELECT File_Path, Comment, Before, After, Deleted_Before, Deleted_After \n\
+             FROM History WHERE Batch = ?1 ORDER BY Id;")
+            .and_then(|mut statement| {
+                statement.query_map(params![batch], |row| {
+                    let file_path: String = row.get(0)?;
+                    let before: String = row.get(2)?;
+                    let after: String = row.get(3)?;
+                    let to_image_data = |index: usize, json: &str| serde_json::from_str::<ImageData>(json)
+                        .map_err(|err| Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(err)));
+                    Ok(HistoryEntry {
+                        file_path: replace_tilde_with_home(&file_path),
+                        comment: row.get(1)?,
+                        before: to_image_data(2, &before)?,
+                        after: to_image_data(3, &after)?,
+                        deleted_before: row.get(4)?,
+                        deleted_after: row.get(5)?,
+                    })
+                })
+                .and_then(|rows| rows.collect::<Result<Vec<HistoryEntry>,Error>>())
+            })
+            .map(|entries| Some((batch, entries))),
+    }
+}
+
+/// the batch of history entries to undo, or to redo
+pub fn select_history_batch(&self, undo: bool) -> Result<Option<(i64, Vec<HistoryEntry>)>> {
+    match self.rusqlite_select_history_batch(undo) {
+        Ok(result) => Ok(result),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+/// mark a batch as undone, or as redone
+pub fn mark_history_batch(&self, batch: i64, undone: bool) -> Result<()> {
+    match self.connection.execute("UPDATE History SET Undone = ?1 WHERE Batch = ?2;", params![undone, batch]) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+/// the picture entry stored for file path, with its tags
+pub fn select_picture_entry(&self, file_path: &str) -> Result<Option<PictureEntry>> {
+    let parameters = vec![Value::from(file_path.to_string()), Value::from(replace_home_with_tilde(file_path))];
+    match self.rusqlite_select_pictures("File_Path IN (?, ?)", &parameters) {
+        Ok(mut picture_entries) => match picture_entries.pop() {
+            Some(mut picture_entry) => match self.entry_tags(&replace_home_with_tilde(file_path)) {
+                Ok(tags) => {
+                    picture_entry.image_data.tags = tags;
+                    Ok(Some(picture_entry))
+                },
+                Err(err) => Err(anyhow!(err)),
+            },
+            None => Ok(None),
+        },
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+fn rusqlite_select_library(&self) -> Result<Library,Error> {
+    let pictures = self.connection.prepare(
+        "SELECT File_Path, File_Size, Colors, Modified_Time, Rank, Palette, Label, Selected, Deleted, Cover, Content_Hash, Perceptual_Hash, Note, \n\
+         Updated_Time, Taken_Time, Camera_Make, Camera_Model, Lens, Exposure, ISO, Focal_Length, Orientation, \n\
+         Latitude, Longitude, Altitude, GPS_Read, \n\
+         Width, Height, Frames \n\
+         FROM Picture ORDER BY File_Path;")
+        .and_then(|mut statement| {
+            statement.query_map([], |row| {
+                let blob: Vec<u8> = row.get(5)?;
+                let mut bytes: [u8;36] = [0;36];
+                let size = blob.len().min(PALETTE_BLOB_SIZE);
+                bytes[..size].copy_from_slice(&blob[..size]);
+                Ok(PictureRecord {
+                    file_path: row.get(0)?,
+                    file_size: row.get(1)?,
+                    colors: row.get(2)?,
+                    modified_time: row.get(3)?,
+                    rank: row.get(4)?,
+                    palette: blob_to_palette(&bytes).iter().map(|color| color.to_string()).collect::<Vec<String>>().join(" "),
+                    label: row.get::<usize, Option<String>>(6)?.unwrap_or_default(),
+                    selected: row.get(7)?,
+                    deleted: row.get(8)?,
+                    cover: row.get(9)?,
+                    content_hash: row.get(10)?,
+                    perceptual_hash: row.get(11)?,
+                    note: row.get::<usize, Option<String>>(12)?.unwrap_or_default(),
+                    updated_time: row.get(13)?,
+                    taken_time: row.get(14)?,
+                    camera_make: row.get(15)?,
+                    camera_model: row.get(16)?,
+                    lens: row.get(17)?,
+                    exposure: row.get(18)?,
+                    iso: row.get(19)?,
+                    focal_length: row.get(20)?,
+                    orientation: row.get(21)?,
+                    latitude: row.get(22)?,
+                    longitude: row.get(23)?,
+                    altitude: row.get(24)?,
+                    gps_read: row.get(25)?,
+                    width: row.get(26)?,
+                    height: row.get(27)?,
+                    frames: row.get(28)?,
+                })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<PictureRecord>,Error>>())
+        })?;
+    let tags = self.rusqlite_select_library_tags()?;
+    let covers = self.rusqlite_select_library_covers()?;
+    Ok(Library { pictures, tags, covers })
+}
+
+fn rusqlite_select_library_tags(&self) -> Result<Vec<TagRecord>,Error> {
+    self.connection.prepare("SELECT File_Path, Label FROM Tag ORDER BY File_Path, Label;")
+        .and_then(|mut statement| {
+            statement.query_map([], |row| Ok(TagRecord { file_path: row.get(0)?, label: row.get(1)? }))
+                .and_then(|rows| rows.collect::<Result<Vec<TagRecord>,Error>>())
+        })
+}
+
+fn rusqlite_select_library_covers(&self) -> Result<Vec<CoverRecord>,Error> {
+    self.connection.prepare("SELECT Dir_Path, File_Name, Rank FROM Cover ORDER BY Dir_Path, File_Name;")
+        .and_then(|mut statement| {
+            statement.query_map([], |row| Ok(CoverRecord { dir_path: row.get(0)?, file_name: row.get(1)?, rank: row.get(2)? }))
+                .and_then(|rows| rows.collect::<Result<Vec<CoverRecord>,Error>>())
+        })
+}
+
+/// all the rows of the Picture, Tag and Cover tables
+pub fn select_library(&self) -> Result<Library> {
+    match self.rusqlite_select_library() {
+        Ok(library) => Ok(library),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+fn palette_from_record(palette: &str) -> Palette {
+    let mut result: Palette = [0;9];
+    for (index, color) in palette.split_whitespace().take(9).enumerate() {
+        result[index] = color.parse::<u32>().unwrap_or_default()
+    };
+    result
+}
+
+fn rusqlite_import_library(&mut self, library: &Library, replace: bool) -> Result<ImportReport,Error> {
+    let mut report = ImportReport::default();
+    // the tags of the pictures left as in the database are left aside too
+    let mut conflicting: HashSet<&str> = HashSet::new();
+    let transaction = self.connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    if replace {
+        transaction.execute_batch("DELETE FROM Picture; DELETE FROM Tag; DELETE FROM Cover;")?;
+    };
+    for picture in &library.pictures {
+        let existing: Option<(i64, String, bool, bool, bool, String)> = transaction.query_row(
+            "SELECT Rank, COALESCE(Label, ''), Selected, Deleted, Cover, COALESCE(Note, '') FROM Picture WHERE File_Path = ?1;",
+            params![picture.file_path],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)))
+            .map(Some)
+            .or_else(|err| if err == Error::QueryReturnedNoRows { Ok(None) } else { Err(err) })?;
+        match existing {
+            Some(curation) if curation != (picture.rank, picture.label.clone(), picture.selected, picture.deleted, picture.cover, picture.note.clone()) => {
+                report.conflicts.push(format!("picture {}: rank, label, selection, deletion, cover or note differ from the database", picture.file_path));
+                conflicting.insert(&picture.file_path);
+            },
+            Some(_) => {},
+            None => {
+                transaction.execute(
+                    "INSERT INTO Picture \n\
+                     (File_Path, File_Size, Colors, Modified_Time, Rank, Palette, Label, Selected, Deleted, Cover, Content_Hash, Perceptual_Hash, Note, \n\
+                     Updated_Time, Taken_Time, Camera_Make, Camera_Model, Lens, Exposure, ISO, Focal_Length, Orientation, \n\
+                     Latitude, Longitude, Altitude, GPS_Read, \n\
+                     Width, Height, Frames) \n\
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29);",
+                    params![
+                    picture.file_path,
+                    picture.file_size,
+                    picture.colors,
+                    picture.modified_time,
+                    picture.rank,
+                    palette_to_blob(&Self::palette_from_record(&picture.palette)),
+                    picture.label,
+                    picture.selected,
+                    picture.deleted,
+                    picture.cover,
+                    picture.content_hash,
+                    picture.perceptual_hash,
+                    picture.note,
+                    picture.updated_time,
+                    picture.taken_time,
+                    picture.camera_make,
+                    picture.camera_model,
+                    picture.lens,
+                    picture.exposure,
+                    picture.iso,
+                    picture.focal_length,
+                    picture.orientation,
+                    picture.latitude,
+                    picture.longitude,
+                    picture.altitude,
+                    picture.gps_read,
+                    picture.width,
+                    picture.height,
+                    picture.frames])?;
+                report.pictures += 1
+            },
+        }
+    };
+    for tag in &library.tags {
+        if conflicting.contains(tag.file_path.as_str()) {
+            report.conflicts.push(format!("tag {} of {}: the picture is left as in the database", tag.label, tag.file_path));
+            continue
+        };
+        let known: bool = transaction.query_row(
+            "SELECT EXISTS (SELECT 1 FROM Picture WHERE File_Path = ?1);",
+            params![tag.file_path],
+            |row| row.get(0))?;
+        if !known {
+            report.conflicts.push(format!("tag {} of {}: the picture is neither in the file nor in the database", tag.label, tag.file_path));
+            continue
+        };
+        report.tags += transaction.execute(
+            "INSERT OR IGNORE INTO Tag (File_Path, Label) VALUES (?1, ?2);",
+            params![tag.file_path, tag.label])?;
+    };
+    for cover in &library.covers {
+        let existing: Option<i64> = transaction.query_row(
+            "SELECT Rank FROM Cover WHERE Dir_Path = ?1 AND File_Name = ?2;",
+            params![cover.dir_path, cover.file_name],
+            |row| row.get(0))
+            .map(Some)
+            .or_else(|err| if err == Error::QueryReturnedNoRows { Ok(None) } else { Err(err) })?;
+        match existing {
+            Some(rank) if rank != cover.rank => {
+                report.conflicts.push(format!("cover {}/{}: rank differs from the database", cover.dir_path, cover.file_name))
+            },
+            Some(_) => {},
+            None => {
+                report.covers += transaction.execute(
+                    "INSERT INTO Cover (Dir_Path, File_Name, Rank) VALUES (?1, ?2, ?3);",
+                    params![cover.dir_path, cover.file_name, cover.rank])?;
+            },
+        }
+    };
+    transaction.commit()?;
+    Ok(report)
+}
+
+/// insert the rows of a library in the database, either merging them with the rows already there,
+/// rows in conflict being left as they are in the database, or replacing all the rows
+pub fn import_library(&mut self, library: &Library, replace: bool) -> Result<ImportReport> {
+    match self.rusqlite_import_library(library, replace) {
+        Ok(report) => Ok(report),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+fn rusqlite_replace_tags(&mut self, sources: &[String], target: Option<&str>) -> Result<Vec<String>,Error> {
+    let transaction = self.connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let mut file_paths: Vec<String> = vec![];
+    for source in sources.iter().filter(|source| Some(source.as_str()) != target) {
+        file_paths.extend(transaction.prepare("SELECT File_Path FROM Tag WHERE Label = ?1 UNION SELECT File_Path FROM Picture WHERE Label = ?1;")?
+            .query_map(params![source], |row| row.get::<usize, String>(0))?
+            .collect::<Result<Vec<String>,Error>>()?);
+        if let Some(target) = target {
+            transaction.execute("INSERT OR IGNORE INTO Tag (File_Path, Label) SELECT File_Path, ?2 FROM Tag WHERE Label = ?1;", params![source, target])?;
+        };
+        transaction.execute("DELETE FROM Tag WHERE Label = ?1;", params![source])?;
+        transaction.execute("UPDATE Picture SET Label = ?2 WHERE Label = ?1;", params![source, target.unwrap_or_default()])?;
+    };
+    file_paths.sort();
+    file_paths.dedup();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+    for file_path in &file_paths {
+        transaction.execute("UPDATE Picture SET Updated_Time = ?1 WHERE File_Path = ?2;", params![now, file_path])?;
+    };
+    transaction.commit()?;
+    Ok(file_paths.iter().map(|file_path| replace_tilde_with_home(file_path)).collect())
+}
+
+/// replace the tags and labels sources with target, or remove them if target is None, in a single
+/// transaction; returns the file paths of the pictures changed
+pub fn replace_tags(&mut self, sources: &[String], target: Option<&str>) -> Result<Vec<String>> {
+    match self.rusqlite_replace_tags(sources, target) {
+        Ok(file_paths) => Ok(file_paths),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+/// make alias stand for tag, replacing what it stood for before
+pub fn set_tag_alias(&self, alias: &str, tag: &str) -> Result<()> {
+    match self.connection.execute("INSERT OR REPLACE INTO Tag_Alias (Alias, Tag) VALUES (?1, ?2);", params![alias, tag]) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+/// delete the alias; returns false if there was no such alias
+pub fn delete_tag_alias(&self, alias: &str) -> Result<bool> {
+    match self.connection.execute("DELETE FROM Tag_Alias WHERE Alias = ?1;", params![alias]) {
+        Ok(count) => Ok(count > 0),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+fn rusqlite_select_tag_aliases(&self) -> Result<BTreeMap<String,String>,Error> {
+    self.connection.prepare("SELECT Alias, Tag FROM Tag_Alias;")
+        .and_then(|mut statement| {
+            statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .and_then(|rows| rows.collect::<Result<BTreeMap<String,String>,Error>>())
+        })
+}
+
+/// the tags that the aliases stand for, by alias
+pub fn select_tag_aliases(&self) -> Result<BTreeMap<String,String>> {
+    match self.rusqlite_select_tag_aliases() {
+        Ok(aliases) => Ok(aliases),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+fn rusqlite_insert_or_replace_saved_search(&self, name: &str, filters: &str) -> Result<(),Error> {
+    self.connection.execute(
+        "INSERT OR REPLACE INTO Saved_Search (Name, Filters) VALUES (?1, ?2);",
+        params![name, filters])
+        .map(|_| ())
+}
+
+/// save the filters under name, replacing the search previously saved under that name
+pub fn save_search(&self, name: &str, filters: &Filters) -> Result<()> {
+    let filters = match serde_json::to_string(filters) {
+        Ok(filters) => filters,
+        Err(err) => return Err(anyhow!(format!("saved search {}: {}", name, err))),
+    };
+    match self.rusqlite_insert_or_replace_saved_search(name, &filters) {
+        Ok(()) => Ok(()),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+fn rusqlite_select_saved_searches(&self) -> Result<Vec<(String, String)>,Error> {
+    self.connection.prepare("SELECT Name, Filters FROM Saved_Search ORDER BY Name;")
+        .and_then(|mut statement| {
+            statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .and_then(|rows| rows.collect())
+        })
+}
+
+/// the saved searches, by name
+pub fn select_saved_searches(&self) -> Result<Vec<(String, Filters)>> {
+    match self.rusqlite_select_saved_searches() {
+        Ok(searches) => searches.into_iter()
+            .map(|(name, filters)| match serde_json::from_str(&filters) {
+                Ok(filters) => Ok((name, filters)),
+                Err(err) => Err(anyhow!(format!("saved search {}: {}", name, err))),
+            })
+            .collect(),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+fn rusqlite_select_saved_search(&self, name: &str) -> Result<Option<String>,Error> {
+    self.connection.query_row(
+        "SELECT Filters FROM Saved_Search WHERE Name = ?1;",
+        params![name],
+        |row| row.get(0))
+        .map(Some)
+        .or_else(|err| if err == Error::QueryReturnedNoRows { Ok(None) } else { Err(err) })
+}
+
+/// the filters of the search saved under name
+pub fn select_saved_search(&self, name: &str) -> Result<Option<Filters>> {
+    match self.rusqlite_select_saved_search(name) {
+        Ok(Some(filters)) => match serde_json::from_str(&filters) {
+            Ok(filters) => Ok(Some(filters)),
+            Err(err) => Err(anyhow!(format!("saved search {}: {}", name, err))),
         },
+        Ok(None) => Ok(None),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+/// delete the search saved under name, returning false if there was none
+pub fn delete_saved_search(&self, name: &str) -> Result<bool> {
+    match self.connection.execute("DELETE FROM Saved_Search WHERE Name = ?1;", params![name]) {
+        Ok(count) => Ok(count > 0),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+fn rusqlite_insert_album_pictures(&mut self, album: &str, file_paths: &[String]) -> Result<usize,Error> {
+    let transaction = self.connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let mut position: i64 = transaction.query_row(
+        "SELECT COALESCE(MAX(Position), 0) FROM Album WHERE Name = ?1;",
+        params![album],
+        |row| row.get(0))?;
+    let mut count: usize = 0;
+    for file_path in file_paths {
+        position += 1;
+        count += transaction.execute(
+            "INSERT OR IGNORE INTO Album (Name, Position, File_Path) VALUES (?1, ?2, ?3);",
+            params![album, position, replace_home_with_tilde(file_path)])?;
+    };
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// append the pictures to the end of the album, creating it if needed; returns the number of pictures
+/// added, those already in the album keeping their position
+pub fn add_to_album(&mut self, album: &str, file_paths: &[String]) -> Result<usize> {
+    match self.rusqlite_insert_album_pictures(album, file_paths) {
+        Ok(count) => Ok(count),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+fn rusqlite_select_album(&self, album: &str) -> Result<Vec<String>,Error> {
+    self.connection.prepare("SELECT File_Path FROM Album WHERE Name = ?1 ORDER BY Position;")
+        .and_then(|mut statement| {
+            statement.query_map(params![album], |row| row.get::<usize, String>(0).map(|file_path| replace_tilde_with_home(&file_path)))
+                .and_then(|rows| rows.collect())
+        })
+}
+
+/// the file paths of the pictures of the album, in the album order
+pub fn select_album(&self, album: &str) -> Result<Vec<String>> {
+    match self.rusqlite_select_album(album) {
+        Ok(file_paths) => Ok(file_paths),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+/// the names of all the albums
+pub fn select_album_names(&self) -> Result<HashSet<String>> {
+    let result = self.connection.prepare("SELECT DISTINCT Name FROM Album;")
+        .and_then(|mut statement| {
+            statement.query_map([], |row| row.get::<usize, String>(0))
+                .and_then(|rows| rows.collect())
+        });
+    match result {
+        Ok(names) => Ok(names),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+fn rusqlite_swap_album_positions(&mut self, album: &str, file_path: &str, other_file_path: &str) -> Result<(),Error> {
+    let transaction = self.connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let position_of = |file_path: &str| transaction.query_row(
+        "SELECT Position FROM Album WHERE Name = ?1 AND File_Path = ?2;",
+        params![album, replace_home_with_tilde(file_path)],
+        |row| row.get::<usize, i64>(0));
+    let position = position_of(file_path)?;
+    let other_position = position_of(other_file_path)?;
+    for (file_path, position) in [(file_path, other_position), (other_file_path, position)] {
+        transaction.execute(
+            "UPDATE Album SET Position = ?1 WHERE Name = ?2 AND File_Path = ?3;",
+            params![position, album, replace_home_with_tilde(file_path)])?;
+    };
+    transaction.commit()
+}
+
+/// exchange the positions of two pictures of the album
+pub fn swap_album_positions(&mut self, album: &str, file_path: &str, other_file_path: &str) -> Result<()> {
+    match self.rusqlite_swap_album_positions(album, file_path, other_file_path) {
+        Ok(()) => Ok(()),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+fn rusqlite_select_stored_pictures(&self) -> Result<Vec<StoredPicture>,Error> {
+    self.connection.prepare("SELECT File_Path, COALESCE(Cover, FALSE), COALESCE(LENGTH(Palette), 0) FROM Picture ORDER BY File_Path;")
+        .and_then(|mut statement| {
+            statement.query_map([], |row| {
+                let palette_size: i64 = row.get(2)?;
+                Ok(StoredPicture { file_path: row.get(0)?, cover: row.get(1)?, palette_size: palette_size as usize })
+            })
+            .and_then(|rows| rows.collect())
+        })
+}
+
+// rows of the full text index that have no picture, and pictures that have no row
+fn rusqlite_count_stale_search_rows(&self) -> Result<usize,Error> {
+    self.connection.query_row(
+        "SELECT                                                                     \n\
+            (SELECT COUNT(*) FROM Picture WHERE File_Path NOT IN (SELECT File_Path FROM Picture_Search)) \n\
+          + (SELECT COUNT(*) FROM Picture_Search WHERE File_Path NOT IN (SELECT File_Path FROM Picture));",
+        [],
+        |row| row.get::<usize, i64>(0))
+        .map(|count| count as usize)
+}
+
+fn rusqlite_repair(&mut self, issues: &[Issue]) -> Result<usize,Error> {
+    let mut count: usize = 0;
+    let transaction = self.connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    for issue in issues {
+        count += match issue {
+            Issue::DuplicatePath(file_path) => {
+                // the row with ~ is the one updated by the program, the other one is dropped
+                transaction.execute(
+                    "DELETE FROM Picture WHERE File_Path = ?1;",
+                    params![replace_tilde_with_home(file_path)])?;
+                1
+            },
+            Issue::AbsolutePath(table, path) => {
+                let tilde_path = replace_home_with_tilde(path);
+                match table {
+                    Table::Picture => {
+                        transaction.execute(
+                            "UPDATE OR IGNORE Picture SET File_Path = ?1 WHERE File_Path = ?2;",
+                            params![tilde_path, path])?;
+                    },
+                    Table::Tag => {
+                        transaction.execute(
+                            "UPDATE OR IGNORE Tag SET File_Path = ?1 WHERE File_Path = ?2;",
+                            params![tilde_path, path])?;
+                        transaction.execute("DELETE FROM Tag WHERE File_Path = ?1;", params![path])?;
+                    },
+                    Table::Cover => {
+                        transaction.execute(
+                            "UPDATE OR IGNORE Cover SET Dir_Path = ?1 WHERE Dir_Path = ?2;",
+                            params![tilde_path, path])?;
+                        transaction.execute("DELETE FROM Cover WHERE Dir_Path = ?1;", params![path])?;
+                    },
+                };
+                1
+            },
+            Issue::OrphanTag(file_path, label) => {
+                transaction.execute(
+                    "DELETE FROM Tag WHERE File_Path IN (?1, ?2) AND Label = ?3;",
+                    params![file_path, replace_home_with_tilde(file_path), label])?;
+                1
+            },
+            Issue::OrphanCover(dir_path, file_name) => {
+                transaction.execute(
+                    "DELETE FROM Cover WHERE Dir_Path IN (?1, ?2) AND File_Name = ?3;",
+                    params![dir_path, replace_home_with_tilde(dir_path), file_name])?;
+                1
+            },
+            // the Cover table is the reference, the Cover column follows it
+            Issue::CoverNotInTable(file_path) | Issue::CoverNotInColumn(file_path) => {
+                transaction.execute(
+                    "UPDATE Picture SET Cover = ?1 WHERE File_Path IN (?2, ?3);",
+                    params![matches!(issue, Issue::CoverNotInColumn(_)), file_path, replace_tilde_with_home(file_path)])?;
+                1
+            },
+            Issue::SearchIndex(_) => {
+                transaction.execute_batch(SEARCH_INDEX_REBUILD)?;
+                1
+            },
+            Issue::BadPalette(file_path, _) => match get_palette_from_picture(&replace_tilde_with_home(file_path)) {
+                Ok((palette, colors)) => {
+                    transaction.execute(
+                        "UPDATE Picture SET Palette = ?1, Colors = ?2 WHERE File_Path = ?3;",
+                        params![palette_to_blob(&palette), colors as i64, file_path])?;
+                    1
+                },
+                Err(err) => {
+                    eprintln!("{}: {}", file_path, err);
+                    0
+                },
+            },
+        }
+    };
+    transaction.commit()?;
+    Ok(count)
+}
+
+/// check the consistency of the Picture, Tag and Cover rows, and if repair, fix what can be fixed;
+/// returns the issues found and the number of issues repaired
+pub fn fsck(&mut self, repair: bool) -> Result<(Vec<Issue>, usize)> {
+    let result = self.rusqlite_select_stored_pictures()
+        .and_then(|pictures| self.rusqlite_select_library_tags().map(|tags| (pictures, tags)))
+        .and_then(|(pictures, tags)| self.rusqlite_select_library_covers().map(|covers| issues(&pictures, &tags, &covers)))
+        .and_then(|mut issues| self.rusqlite_count_stale_search_rows().map(|count| {
+            if count > 0 {
+                issues.push(Issue::SearchIndex(count))
+            };
+            issues
+        }))
+        .and_then(|issues| if repair {
+            self.rusqlite_repair(&issues).map(|count| (issues, count))
+        } else {
+            Ok((issues, 0))
+        });
+    match result {
+        Ok(result) => Ok(result),
         Err(err) => Err(anyhow!(err)),
     }
 }
@@ -595,18 +1660,26 @@ pub fn insert_new_picture_with_file_path(&self, picture_entry: &PictureEntry, fi
             label: picture_entry.label().unwrap_or_default(),
             cover: false,
             tags: picture_entry.image_data.tags.clone(),
+            note: picture_entry.image_data.note.clone(),
+            perceptual_hash: picture_entry.image_data.perceptual_hash,
         },
         false);
+    let mut new_entry = new_entry;
+    new_entry.content_hash = picture_entry.content_hash.clone();
+    new_entry.exif = picture_entry.exif.clone();
+    new_entry.width = picture_entry.width;
+    new_entry.height = picture_entry.height;
+    new_entry.frames = picture_entry.frames;
     self.insert_new_picture_entry(new_entry)
 }
 
 
 pub fn retrieve_or_insert_picture_entry(&self, file_path: &str) -> Result<Option<PictureEntry>> {
-    match self.connection.prepare(" SELECT File_Path, File_Size, Colors, Modified_Time, Rank, Palette, Label, Selected, Deleted, Cover, rowid FROM Picture WHERE File_Path = ?1;") {
-        Ok(mut statement) => match statement.query([file_path]) {
+    match self.connection.prepare(&format!("SELECT {} FROM Picture WHERE File_Path IN (?1, ?2);", PICTURE_COLUMNS)) {
+        Ok(mut statement) => match statement.query([file_path.to_string(), replace_home_with_tilde(file_path)]) {
             Ok(mut rows) => match rows.next() {
                 Ok(Some(row)) => match Self::sql_to_picture_entry(row) {
-                    Ok(mut entry) => match self.entry_tags(&entry.file_path) {
+                    Ok(mut entry) => match self.entry_tags(&replace_home_with_tilde(&entry.file_path)) {
                         Ok(labels) => {
                             entry.image_data.tags = labels;
                             Ok(Some(entry))
@@ -634,3 +1707,379 @@ pub fn retrieve_or_insert_picture_entry(&self, file_path: &str) -> Result<Option
 }
 }
 
+// distance_km(LAT1, LON1, LAT2, LON2) in SQL, for the near: queries
+fn add_distance_function(connection: &Connection) -> Result<(),Error> {
+    connection.create_scalar_function(
+        "distance_km",
+        4,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |context| {
+            let latitude_a = context.get::<Option<f64>>(0)?;
+            let longitude_a = context.get::<Option<f64>>(1)?;
+            let latitude_b = context.get::<Option<f64>>(2)?;
+            let longitude_b = context.get::<Option<f64>>(3)?;
+            match (latitude_a, longitude_a, latitude_b, longitude_b) {
+                (Some(latitude_a), Some(longitude_a), Some(latitude_b), Some(longitude_b)) => Ok(Some(distance_km(latitude_a, longitude_a, latitude_b, longitude_b))),
+                _ => Ok(None),
+            }
+        })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::comment::Comment;
+    use crate::picture_io::write_image_data;
+    use crate::sync::{sync, SyncMode};
+    use std::fs;
+
+    fn my_database() -> Database {
+        let mut database = Database::from_path(":memory:").expect("can't open in memory database");
+        database.migrate().expect("can't migrate database");
+        database
+    }
+
+    fn my_image_data(label: &str) -> ImageData {
+        ImageData { label: label.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn migrating_a_new_database_brings_it_to_the_current_schema_version() {
+        let database = my_database();
+        assert_eq!(schema_version(), database.rusqlite_schema_version().unwrap());
+        assert!(database.select_all_picture_file_paths().unwrap().is_empty());
+    }
+
+    #[test]
+    fn migrating_twice_changes_nothing() {
+        let mut database = my_database();
+        assert!(database.migrate().is_ok());
+        assert_eq!(schema_version(), database.rusqlite_schema_version().unwrap());
+    }
+
+    #[test]
+    fn rehashing_fills_missing_content_hashes() {
+        let database = my_database();
+        let file_path = std::fs::canonicalize("testdata/ChessSet.jpg").unwrap().display().to_string();
+        let mut picture_entry = make_picture_entry(file_path.clone(), 0, UNIX_EPOCH, my_image_data(""), false);
+        picture_entry.content_hash = None;
+        database.insert_new_picture_entry(picture_entry).unwrap();
+        assert_eq!(1, database.rehash().unwrap());
+        assert_eq!(0, database.rehash().unwrap());
+        let entries = database.select_pictures(&Query::All).unwrap();
+        assert_eq!(content_hash(&file_path).ok(), entries[0].content_hash);
+    }
+
+    #[test]
+    fn missing_exif_metadata_and_dimensions_are_read_once_and_stored() {
+        let database = my_database();
+        let file_path = std::fs::canonicalize("testdata/nature/rose.jpeg").unwrap().display().to_string();
+        let picture_entry = make_picture_entry(file_path.clone(), 0, UNIX_EPOCH, my_image_data(""), false);
+        database.insert_new_picture_entry(picture_entry).unwrap();
+        assert_eq!(1, database.read_missing_exif().unwrap());
+        assert_eq!(0, database.read_missing_exif().unwrap());
+        let entries = database.select_pictures(&Query::All).unwrap();
+        assert_eq!(ExifData::from_file(&file_path), entries[0].exif);
+        assert_eq!(Some(String::from("Canon PowerShot G3")), entries[0].exif.model);
+        database.connection.execute("UPDATE Picture SET GPS_Read = 0;", []).unwrap();
+        assert_eq!(1, database.read_missing_exif().unwrap());
+        assert_eq!(0, database.read_missing_exif().unwrap());
+        assert_eq!(None, entries[0].dimensions());
+        assert_eq!(1, database.read_missing_dimensions().unwrap());
+        assert_eq!(0, database.read_missing_dimensions().unwrap());
+        let entries = database.select_pictures(&Query::All).unwrap();
+        assert_eq!(read_dimensions(&file_path).ok(), entries[0].dimensions());
+        assert_eq!(Some(1), entries[0].frames);
+        assert!(!entries[0].is_animated());
+    }
+
+    #[test]
+    fn a_moved_picture_is_matched_by_size_and_hash_and_keeps_its_data() {
+        let mut database = my_database();
+        let file_path = std::fs::canonicalize("testdata/ChessSet.jpg").unwrap().display().to_string();
+        let old_file_path = "/nowhere/ChessSet.jpg";
+        let mut picture_entry = make_picture_entry(old_file_path.to_string(), read_file_info(&file_path).unwrap().0, UNIX_EPOCH, ImageData { rank: Rank::TwoStars, ..my_image_data("") }, false);
+        picture_entry.content_hash = content_hash(&file_path).ok();
+        database.insert_new_picture_entry(picture_entry).unwrap();
+        database.rusqlite_insert_tag_label(old_file_path, "chess").unwrap();
+        let directory = file_path_directory(&file_path);
+        let matches = database.reconcile_matches(&directory).unwrap();
+        assert_eq!(vec![(old_file_path.to_string(), file_path.clone())], matches);
+        database.move_picture(old_file_path, &file_path).unwrap();
+        let entries = database.select_pictures(&Query::All).unwrap();
+        assert_eq!(file_path, entries[0].file_path);
+        assert_eq!(Rank::TwoStars, entries[0].image_data.rank);
+        assert!(database.select_tags_by_file_path().unwrap()[&file_path].contains("chess"));
+        assert!(database.reconcile_matches(&directory).unwrap().is_empty());
+    }
+
+    #[test]
+    fn history_batches_are_undone_last_first_and_redone_first_first() {
+        let mut database = my_database();
+        let before = make_picture_entry(String::from("/photos/sunset.jpg"), 0, UNIX_EPOCH, my_image_data(""), false);
+        let mut ranked = before.clone();
+        ranked.image_data.rank = Rank::ThreeStars;
+        let mut deleted = ranked.clone();
+        deleted.deleted = true;
+        database.insert_history_batch(&[HistoryEntry::new(&Comment::Rank { rank: Rank::ThreeStars }, &before, &ranked)]).unwrap();
+        database.insert_history_batch(&[HistoryEntry::new(&Comment::ToggleDelete, &ranked, &deleted)]).unwrap();
+        let (batch, entries) = database.select_history_batch(true).unwrap().unwrap();
+        assert_eq!(2, batch);
+        assert_eq!((ranked.image_data.clone(), false), entries[0].restored(true));
+        database.mark_history_batch(batch, true).unwrap();
+        let (batch, _) = database.select_history_batch(true).unwrap().unwrap();
+        assert_eq!(1, batch);
+        database.mark_history_batch(batch, true).unwrap();
+        assert!(database.select_history_batch(true).unwrap().is_none());
+        let (batch, entries) = database.select_history_batch(false).unwrap().unwrap();
+        assert_eq!(1, batch);
+        assert_eq!((ranked.image_data.clone(), false), entries[0].restored(false));
+        database.mark_history_batch(batch, false).unwrap();
+        database.insert_history_batch(&[HistoryEntry::new(&Comment::Unlabel, &ranked, &ranked)]).unwrap();
+        assert!(database.select_history_batch(false).unwrap().is_none());
+    }
+
+    #[test]
+    fn importing_an_exported_library_merges_or_replaces_rows() {
+        let mut database = my_database();
+        let mut picture_entry = make_picture_entry(String::from("~/photos/sunset.jpg"), 4807, UNIX_EPOCH, ImageData {
+            colors: 12,
+            rank: Rank::TwoStars,
+            palette: [1,2,3,4,5,6,7,8,9],
+            cover: true,
+            perceptual_hash: Some(u64::MAX),
+            ..my_image_data("holidays")
+        }, false);
+        picture_entry.content_hash = Some(String::from("af1349b9"));
+        database.insert_new_picture_entry(picture_entry).unwrap();
+        database.rusqlite_insert_tag_label("~/photos/sunset.jpg", "sea").unwrap();
+        database.insert_or_update_cover("~/photos", "sunset.jpg", Rank::TwoStars).unwrap();
+        database.connection.execute("UPDATE Picture SET Updated_Time = 1719000100;", []).unwrap();
+        let library = database.select_library().unwrap();
+        assert_eq!(Some(1719000100), library.pictures[0].updated_time);
+        assert_eq!("1 2 3 4 5 6 7 8 9", library.pictures[0].palette);
+
+        let mut other = my_database();
+        let report = other.import_library(&library, false).unwrap();
+        assert_eq!((1, 1, 1), (report.pictures, report.tags, report.covers));
+        assert!(report.conflicts.is_empty());
+        assert_eq!(library, other.select_library().unwrap());
+
+        let mut changed = library.clone();
+        changed.pictures[0].rank = 0;
+        changed.covers[0].rank = 0;
+        changed.tags.push(TagRecord { file_path: String::from("~/photos/sunset.jpg"), label: String::from("sunset") });
+        changed.tags.push(TagRecord { file_path: String::from("~/photos/gone.jpg"), label: String::from("sea") });
+        let report = other.import_library(&changed, false).unwrap();
+        assert_eq!((0, 0, 0), (report.pictures, report.tags, report.covers));
+        assert_eq!(5, report.conflicts.len());
+        assert_eq!(library, other.select_library().unwrap());
+
+        changed.tags.pop();
+        let report = other.import_library(&changed, true).unwrap();
+        assert_eq!((1, 2, 1), (report.pictures, report.tags, report.covers));
+        assert_eq!(changed, other.select_library().unwrap());
+    }
+
+    #[test]
+    fn a_database_newer_than_the_program_is_refused() {
+        let mut database = my_database();
+        database.connection.execute("INSERT INTO Schema_Version (Version) VALUES (?1);", params![schema_version() as i64 + 1]).unwrap();
+        assert!(database.migrate().is_err());
+    }
+
+    #[test]
+    fn fsck_repairs_what_drifted_between_the_tables() {
+        let mut database = my_database();
+        let home = std::env::home_dir().unwrap().display().to_string();
+        let absolute = format!("{}/photos/sunset.jpg", home);
+        for (file_path, cover) in [("~/photos/sunset.jpg", false), (absolute.as_str(), false), ("~/photos/dawn.jpg", true)] {
+            database.connection.execute(
+                "INSERT INTO Picture (File_Path, File_Size, Colors, Modified_Time, Rank, Palette, Label, Selected, Deleted, Cover) \n\
+                 VALUES (?1, 0, 0, 0, 0, ?2, '', FALSE, FALSE, ?3);",
+                params![file_path, palette_to_blob(&[0;9]), cover]).unwrap();
+        };
+        database.connection.execute_batch(&format!(
+            "INSERT INTO Tag VALUES ('{}/photos/dawn.jpg', 'sea'); \n\
+             INSERT INTO Tag VALUES ('~/photos/gone.jpg', 'sea'); \n\
+             INSERT INTO Cover VALUES ('~/photos', 'sunset.jpg', 1);", home)).unwrap();
+        let (issues, _) = database.fsck(false).unwrap();
+        assert_eq!(6, issues.len());
+        let (_, repaired) = database.fsck(true).unwrap();
+        assert_eq!(6, repaired);
+        assert!(database.fsck(false).unwrap().0.is_empty());
+        let library = database.select_library().unwrap();
+        assert_eq!(vec!["~/photos/dawn.jpg", "~/photos/sunset.jpg"], library.pictures.iter().map(|picture| picture.file_path.as_str()).collect::<Vec<&str>>());
+        assert!(library.pictures[1].cover);
+        assert!(!library.pictures[0].cover);
+        assert_eq!(vec![TagRecord { file_path: String::from("~/photos/dawn.jpg"), label: String::from("sea") }], library.tags);
+    }
+
+    #[test]
+    fn a_failed_batch_update_is_rolled_back() {
+        let file_path = std::env::temp_dir().join("gsr_transaction_test.db");
+        let _ = std::fs::remove_file(&file_path);
+        let mut database = Database::from_path(&file_path.display().to_string()).unwrap();
+        database.migrate().unwrap();
+        let journal_mode: String = database.connection.query_row("PRAGMA journal_mode;", [], |row| row.get(0)).unwrap();
+        assert_eq!("wal", journal_mode);
+        database.begin_transaction().unwrap();
+        let result = database.connection.execute("INSERT INTO Tag VALUES ('~/photos/sunset.jpg', 'sea');", [])
+            .map_err(|err| anyhow!(err))
+            .and_then(|_| Err::<(), anyhow::Error>(anyhow!("failure")));
+        assert!(database.end_transaction(result).is_err());
+        assert!(database.select_tags_by_file_path().unwrap().is_empty());
+        database.begin_transaction().unwrap();
+        let result = database.connection.execute("INSERT INTO Tag VALUES ('~/photos/sunset.jpg', 'sea');", []).map_err(|err| anyhow!(err));
+        assert!(database.end_transaction(result).is_ok());
+        assert_eq!(1, database.select_tags_by_file_path().unwrap().len());
+        drop(database);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", file_path.display(), suffix));
+        }
+    }
+
+    #[test]
+    fn searches_are_saved_replaced_and_deleted_by_name() {
+        let database = my_database();
+        let filters = Filters { query: Some(String::from("rank>=2")), order: Some(String::from("Date")), ..Filters::default() };
+        database.save_search("daily", &Filters::default()).unwrap();
+        database.save_search("daily", &filters).unwrap();
+        database.save_search("all", &Filters::default()).unwrap();
+        assert_eq!(vec![String::from("all"), String::from("daily")], database.select_saved_searches().unwrap().into_iter().map(|(name, _)| name).collect::<Vec<String>>());
+        assert_eq!(Some(filters), database.select_saved_search("daily").unwrap());
+        assert!(database.delete_saved_search("daily").unwrap());
+        assert!(!database.delete_saved_search("daily").unwrap());
+        assert_eq!(None, database.select_saved_search("daily").unwrap());
+    }
+
+    #[test]
+    fn albums_keep_the_order_pictures_are_added_and_moved_in() {
+        let mut database = my_database();
+        let file_paths: Vec<String> = ["/photos/a.jpg", "/photos/b.jpg", "/photos/c.jpg"].iter().map(|file_path| file_path.to_string()).collect();
+        assert_eq!(2, database.add_to_album("show", &file_paths[1..]).unwrap());
+        assert_eq!(1, database.add_to_album("show", &file_paths[..2]).unwrap());
+        assert_eq!(vec!["/photos/b.jpg", "/photos/c.jpg", "/photos/a.jpg"], database.select_album("show").unwrap());
+        database.swap_album_positions("show", "/photos/c.jpg", "/photos/a.jpg").unwrap();
+        assert_eq!(vec!["/photos/b.jpg", "/photos/a.jpg", "/photos/c.jpg"], database.select_album("show").unwrap());
+        assert_eq!(HashSet::from([String::from("show")]), database.select_album_names().unwrap());
+        database.delete_picture("/photos/a.jpg").unwrap();
+        assert_eq!(vec!["/photos/b.jpg", "/photos/c.jpg"], database.select_album("show").unwrap());
+    }
+
+    #[test]
+    fn the_full_text_index_follows_pictures_labels_and_tags() {
+        let mut database = my_database();
+        let mut picture_entry = make_picture_entry(String::from("/photos/Paris/IMG_0001.jpg"), 0, UNIX_EPOCH, my_image_data("night"), false);
+        database.insert_new_picture_entry(picture_entry.clone()).unwrap();
+        let found = |database: &Database, text: &str| database.select_pictures(&Query::Text { text: text.to_string() }).unwrap().len();
+        assert_eq!(1, found(&database, "paris nig"));
+        database.insert_new_picture_entry(make_picture_entry(String::from("/photos/Rome/IMG_0002.jpg"), 0, UNIX_EPOCH, my_image_data("day"), false)).unwrap();
+        database.connection.execute("UPDATE Picture SET rowid = rowid + 100 WHERE File_Path = '/photos/Paris/IMG_0001.jpg';", []).unwrap();
+        assert_eq!("/photos/Paris/IMG_0001.jpg", database.select_pictures(&Query::Text { text: String::from("night") }).unwrap()[0].file_path);
+        database.delete_picture("/photos/Rome/IMG_0002.jpg").unwrap();
+        picture_entry.image_data.label = String::from("day");
+        picture_entry.image_data.tags = HashSet::from([String::from("seine")]);
+        picture_entry.set_note("Anna's birthday, à Séville");
+        database.update_picture_entry(&picture_entry).unwrap();
+        assert_eq!(0, found(&database, "paris nig"));
+        assert_eq!(1, found(&database, "day seine"));
+        assert_eq!(1, found(&database, "anna sév"));
+        assert_eq!("Anna's birthday, à Séville", database.select_pictures(&Query::Text { text: String::from("birthday") }).unwrap()[0].image_data.note);
+        database.connection.execute("DELETE FROM Picture_Search;", []).unwrap();
+        assert_eq!(vec![Issue::SearchIndex(1)], database.fsck(true).unwrap().0);
+        assert_eq!(1, found(&database, "day seine"));
+        database.delete_picture("/photos/Paris/IMG_0001.jpg").unwrap();
+        assert_eq!(0, found(&database, "paris"));
+        assert!(database.fsck(false).unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn tags_and_labels_are_renamed_merged_and_deleted_in_the_whole_library() {
+        let mut database = my_database();
+        for (file_path, label, tags) in [("/photos/a.jpg", "beech", vec!["sea"]), ("/photos/b.jpg", "", vec!["beech", "beach"]), ("/photos/c.jpg", "ocean", vec![])] {
+            database.insert_new_picture_entry(make_picture_entry(file_path.to_string(), 0, UNIX_EPOCH, my_image_data(label), false)).unwrap();
+            for tag in tags {
+                database.connection.execute("INSERT INTO Tag (File_Path, Label) VALUES (?1, ?2);", params![file_path, tag]).unwrap();
+            }
+        };
+        let tags_of = |database: &Database, file_path: &str| {
+            let picture_entry = database.select_picture_entry(file_path).unwrap().unwrap();
+            let mut tags: Vec<String> = picture_entry.image_data.tags.into_iter().collect();
+            tags.sort();
+            (picture_entry.image_data.label, tags)
+        };
+        assert_eq!(vec![String::from("/photos/a.jpg"), String::from("/photos/b.jpg")], database.replace_tags(&[String::from("beech")], Some("beach")).unwrap());
+        assert_eq!((String::from("beach"), vec![String::from("sea")]), tags_of(&database, "/photos/a.jpg"));
+        assert_eq!((String::new(), vec![String::from("beach")]), tags_of(&database, "/photos/b.jpg"));
+        assert_eq!(2, database.replace_tags(&[String::from("sea"), String::from("ocean")], Some("water")).unwrap().len());
+        assert_eq!((String::from("beach"), vec![String::from("water")]), tags_of(&database, "/photos/a.jpg"));
+        assert_eq!((String::from("water"), vec![]), tags_of(&database, "/photos/c.jpg"));
+        assert_eq!(2, database.replace_tags(&[String::from("beach")], None).unwrap().len());
+        assert_eq!((String::new(), vec![String::from("water")]), tags_of(&database, "/photos/a.jpg"));
+        assert_eq!(2, database.select_pictures(&Query::Text { text: String::from("water") }).unwrap().len());
+    }
+
+    #[test]
+    fn pictures_are_selected_by_distance_to_a_point_or_inside_a_box() {
+        let database = my_database();
+        for (file_path, latitude, longitude) in [("/photos/eiffel.jpg", Some(48.8584), Some(2.2945)), ("/photos/louvre.jpg", Some(48.8606), Some(2.3376)), ("/photos/london.jpg", Some(51.5007), Some(-0.1246)), ("/photos/nowhere.jpg", None, None)] {
+            database.connection.execute(
+                "INSERT INTO Picture (File_Path, File_Size, Colors, Modified_Time, Rank, Palette, Label, Selected, Deleted, Cover, Latitude, Longitude) \n\
+                 VALUES (?1, 0, 0, 0, 0, ?2, '', false, false, false, ?3, ?4);",
+                params![file_path, palette_to_blob(&[0;9]), latitude, longitude]).unwrap();
+        };
+        let found = |source: &str| database.select_pictures(&Query::parse(source).unwrap()).unwrap().len();
+        assert_eq!(2, found("near:48.8584,2.2945,5"));
+        assert_eq!(1, found("near:48.8584,2.2945,1"));
+        assert_eq!(3, found("near:48.8584,2.2945,400"));
+        assert_eq!(1, found("bbox:51,-1,52,0"));
+        assert_eq!(3, found("bbox:40,-10,60,10"));
+    }
+
+    #[test]
+    fn parent_tags_select_their_descendants_and_aliases_are_stored() {
+        let database = my_database();
+        for (file_path, tag) in [("/photos/a.jpg", "place/france/paris"), ("/photos/b.jpg", "place/usa/new-york"), ("/photos/c.jpg", "placebo")] {
+            database.connection.execute(
+                "INSERT INTO Picture (File_Path, File_Size, Colors, Modified_Time, Rank, Palette, Label, Selected, Deleted, Cover) \n\
+                 VALUES (?1, 0, 0, 0, 0, ?2, '', false, false, false);",
+                params![file_path, palette_to_blob(&[0;9])]).unwrap();
+            database.connection.execute("INSERT INTO Tag (File_Path, Label) VALUES (?1, ?2);", params![file_path, tag]).unwrap();
+        };
+        let found = |label: &str| database.select_pictures(&Query::Tag { label: label.to_string() }).unwrap().len();
+        assert_eq!(2, found("place"));
+        assert_eq!(1, found("place/france"));
+        assert_eq!(0, found("place/fr"));
+        database.set_tag_alias("nyc", "place/usa/ny").unwrap();
+        database.set_tag_alias("nyc", "place/usa/new-york").unwrap();
+        assert_eq!(Some(&String::from("place/usa/new-york")), database.select_tag_aliases().unwrap().get("nyc"));
+        assert!(database.delete_tag_alias("nyc").unwrap());
+        assert!(!database.delete_tag_alias("nyc").unwrap());
+        assert!(database.select_tag_aliases().unwrap().is_empty());
+    }
+
+    #[test]
+    fn a_cover_synced_from_its_sidecar_updates_the_cover_table() {
+        let directory = std::env::temp_dir().join("gsr_sync_cover");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let file_path = directory.join("flower.jpg").display().to_string();
+        fs::copy("testdata/nature/flower.jpg", &file_path).unwrap();
+        let mut database = my_database();
+        let picture_entry = make_picture_entry(file_path.clone(), 0, UNIX_EPOCH, my_image_data(""), false);
+        let sidecar_file_path = picture_entry.image_data_file_path();
+        database.insert_new_picture_entry(picture_entry).unwrap();
+        let mut sidecar = my_image_data("");
+        sidecar.cover = true;
+        write_image_data(&sidecar, &sidecar_file_path).unwrap();
+        assert_eq!(1, sync(&mut database, SyncMode::SidecarToDb, false).unwrap());
+        assert_eq!(1, database.select_cover_picture_entries().unwrap().len());
+        sidecar.cover = false;
+        write_image_data(&sidecar, &sidecar_file_path).unwrap();
+        assert_eq!(1, sync(&mut database, SyncMode::SidecarToDb, false).unwrap());
+        assert!(database.select_cover_picture_entries().unwrap().is_empty());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
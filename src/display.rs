@@ -22,6 +22,11 @@ pub fn editor_input_display(editor: &Editor) -> String {
             Some(InputKind::Label) => format!("label:{} {}", editor.input(), editor.candidates()),
             Some(InputKind::Relabel) => format!("relabel:{} {}", editor.input(), editor.candidates()),
             Some(InputKind::Index) => format!("index:{}", editor.input()),
+            Some(InputKind::SavedSearch) => format!("saved search:{} {}", editor.input(), editor.candidates()),
+            Some(InputKind::Album) => format!("album:{} {}", editor.input(), editor.candidates()),
+            Some(InputKind::Note) => format!("note:{}", editor.input()),
+            Some(InputKind::RenameLabel) => format!("rename label as:{} {}", editor.input(), editor.candidates()),
+            Some(InputKind::Radius) => format!("near, km:{}", editor.input()),
             None => String::from(""),
     }
 }
@@ -103,5 +108,11 @@ pub fn info(catalog: &Catalog) {
 }
 
 pub fn picture_info_display(entry: &PictureEntry) -> String {
-    format!("{}\n{} bytes\nlabel: {}\ntags:{:#?}", entry.file_path, entry.file_size, entry.image_data.label, entry.image_data.tags)
+    let exif = entry.exif.display();
+    let dimensions = match entry.dimensions() {
+        Some((width, height)) => format!(", {}x{} pixels, {:.1} MP", width, height, entry.pixels() as f64 / 1_000_000.0),
+        None => String::new(),
+    };
+    format!("{}\n{} bytes{}\nlabel: {}\ntags:{:#?}\nnote: {}{}{}", entry.file_path, entry.file_size, dimensions, entry.image_data.label, entry.image_data.tags, entry.image_data.note,
+        if exif.is_empty() { "" } else { "\n" }, exif)
 }
@@ -4,7 +4,7 @@ use std::collections::HashSet;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum InputKind {
-   AddTag, DeleteTag, Search, SearchLabel, Label, Relabel, Index, }
+   AddTag, DeleteTag, Search, SearchLabel, Label, Relabel, Index, SavedSearch, Album, Note, RenameLabel, Radius, }
 
 pub struct Editor {
     input: Option<String>,
@@ -41,6 +41,12 @@ impl Editor {
         self.completion = false
     }
 
+    /// begin an input that starts with the given text, e.g. the note being edited
+    pub fn begin_input_with_text(&mut self, kind: InputKind, text: &str) {
+        self.begin_input(kind, HashSet::new());
+        self.input = Some(text.to_string())
+    }
+
     pub fn editing(&self) -> bool {
         self.input_kind.is_some()
     }
@@ -69,7 +75,9 @@ impl Editor {
                     let _ = catalog.untag_current_entry(input);
                 },
                 InputKind::Search => {
-                    catalog.move_to_input_pattern(input);
+                    if let Err(err) = catalog.search_text(input) {
+                        eprintln!("{}", err)
+                    }
                 },
                 InputKind::SearchLabel => {
                     catalog.move_to_label_pattern(input);
@@ -87,6 +95,32 @@ impl Editor {
                 InputKind::Relabel => {
                     let _ = catalog.set_selected_labels_with_input(input);
                 },
+                InputKind::SavedSearch => {
+                    if let Err(err) = catalog.load_saved_search(input) {
+                        eprintln!("{}", err)
+                    }
+                },
+                InputKind::Album => {
+                    if let Err(err) = catalog.add_to_album(input) {
+                        eprintln!("{}", err)
+                    }
+                },
+                InputKind::RenameLabel => {
+                    if let Err(err) = catalog.rename_current_label(input) {
+                        eprintln!("{}", err)
+                    }
+                },
+                InputKind::Note => {
+                    if let Err(err) = catalog.note_current_entry(input) {
+                        eprintln!("{}", err)
+                    }
+                },
+                InputKind::Radius => match input.parse::<f64>() {
+                    Ok(radius) => if let Err(err) = catalog.show_near_current_entry(radius) {
+                        eprintln!("{}", err)
+                    },
+                    Err(_) => eprintln!("radius should be a number of kilometers: {}", input),
+                },
             }
         }
         self.completion = false;
@@ -106,8 +140,11 @@ impl Editor {
         if let Some(kind) = self.input_kind.clone() {
             let ch_is_ok: bool = match kind {
                 InputKind::Index => ch.is_ascii_digit(),
-                InputKind::AddTag | InputKind::DeleteTag | InputKind::Label | InputKind::Relabel | InputKind::SearchLabel => matches!(ch, 'a'..='z' | '0'..='9' | '-' | '_'),
+                InputKind::AddTag | InputKind::DeleteTag | InputKind::Label | InputKind::Relabel | InputKind::SearchLabel | InputKind::RenameLabel => matches!(ch, 'a'..='z' | '0'..='9' | '-' | '_' | '/'),
+                InputKind::SavedSearch | InputKind::Album => matches!(ch, 'a'..='z' | '0'..='9' | '-' | '_'),
                 InputKind::Search => true,
+                InputKind::Note => !ch.is_control(),
+                InputKind::Radius => ch.is_ascii_digit() || ch == '.',
             };
             if ch_is_ok {
                 self.input = self.input.clone().map( |s| {
@@ -122,7 +159,7 @@ impl Editor {
 
     pub fn complete(&mut self) {
         if let Some(kind) = self.input_kind.clone(){
-            if [InputKind::AddTag,InputKind::DeleteTag,InputKind::Label,InputKind::Relabel,InputKind::SearchLabel].contains(&kind) {
+            if [InputKind::AddTag,InputKind::DeleteTag,InputKind::Label,InputKind::Relabel,InputKind::SearchLabel,InputKind::SavedSearch,InputKind::Album,InputKind::RenameLabel].contains(&kind) {
                 if let Some(prefix) = &self.input {
                     let candidates = candidates(prefix, &self.tags);
                     match candidates.len() {
@@ -159,3 +196,14 @@ impl Editor {
         assert_eq!(false, editor.editing());
     }
 
+#[cfg(test)]
+    #[test]
+    fn editing_a_note_accepts_spaces_and_unicode() {
+        let mut editor = Editor::new();
+        editor.begin_input_with_text(InputKind::Note, "Anna");
+        for ch in " à Séville\t".chars() {
+            editor.append(ch)
+        };
+        assert_eq!(String::from("Anna à Séville"), editor.input());
+    }
+
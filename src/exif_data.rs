@@ -0,0 +1,170 @@
+use chrono::NaiveDate;
+use exif::{DateTime, Exif, In, Reader, Tag, Value};
+use std::fs::File;
+use std::io::BufReader;
+
+/// orientation of a picture that is displayed as stored
+pub const NORMAL_ORIENTATION: u16 = 1;
+
+/// the EXIF metadata of a picture, as far as the gallery is concerned
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct ExifData {
+    /// date and time the picture was taken, in seconds since epoch, the time zone being ignored
+    pub taken: Option<i64>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub lens: Option<String>,
+    /// exposure time, e.g. 1/250 s
+    pub exposure: Option<String>,
+    pub iso: Option<u32>,
+    /// focal length in millimeters
+    pub focal_length: Option<f64>,
+    /// EXIF orientation, from 1 to 8, None if the picture was not read yet
+    pub orientation: Option<u16>,
+    /// GPS position in degrees, north and east being positive
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// GPS altitude in meters, negative below sea level
+    pub altitude: Option<f64>,
+}
+
+impl ExifData {
+    /// the metadata of a picture file; a picture without EXIF has no metadata but the normal orientation
+    pub fn from_file(file_path: &str) -> ExifData {
+        let exif = File::open(file_path)
+            .ok()
+            .and_then(|file| Reader::new().read_from_container(&mut BufReader::new(file)).ok());
+        match exif {
+            Some(exif) => ExifData::from_exif(&exif),
+            None => ExifData { orientation: Some(NORMAL_ORIENTATION), ..ExifData::default() },
+        }
+    }
+
+    fn from_exif(exif: &Exif) -> ExifData {
+        let text = |tag: Tag| match exif.get_field(tag, In::PRIMARY).map(|field| &field.value) {
+            Some(Value::Ascii(strings)) => strings.first()
+                .map(|bytes| String::from_utf8_lossy(bytes).trim().to_string())
+                .filter(|string| !string.is_empty()),
+            _ => None,
+        };
+        let uint = |tag: Tag| exif.get_field(tag, In::PRIMARY).and_then(|field| field.value.get_uint(0));
+        let rationals = |tag: Tag| match exif.get_field(tag, In::PRIMARY).map(|field| &field.value) {
+            Some(Value::Rational(rationals)) => rationals.iter().map(|rational| rational.to_f64()).collect::<Vec<f64>>(),
+            _ => vec![],
+        };
+        // degrees, minutes and seconds, negative toward the reference given as negative
+        let coordinate = |tag: Tag, reference: Tag, negative: &str| match rationals(tag)[..] {
+            [degrees, minutes, seconds] if degrees.is_finite() && minutes.is_finite() && seconds.is_finite() => {
+                let value = degrees + minutes / 60.0 + seconds / 3600.0;
+                Some(if text(reference).is_some_and(|reference| reference.eq_ignore_ascii_case(negative)) { -value } else { value })
+            },
+            _ => None,
+        };
+        ExifData {
+            taken: match exif.get_field(Tag::DateTimeOriginal, In::PRIMARY).map(|field| &field.value) {
+                Some(Value::Ascii(strings)) => strings.first()
+                    .and_then(|bytes| DateTime::from_ascii(bytes).ok())
+                    .and_then(|date_time| NaiveDate::from_ymd_opt(date_time.year as i32, date_time.month as u32, date_time.day as u32)
+                        .and_then(|date| date.and_hms_opt(date_time.hour as u32, date_time.minute as u32, date_time.second as u32)))
+                    .map(|date_time| date_time.and_utc().timestamp()),
+                _ => None,
+            },
+            make: text(Tag::Make),
+            model: text(Tag::Model),
+            lens: text(Tag::LensModel),
+            exposure: exif.get_field(Tag::ExposureTime, In::PRIMARY).map(|field| field.display_value().with_unit(exif).to_string()),
+            iso: uint(Tag::PhotographicSensitivity),
+            focal_length: match exif.get_field(Tag::FocalLength, In::PRIMARY).map(|field| &field.value) {
+                Some(Value::Rational(rationals)) => rationals.first().map(|rational| rational.to_f64()),
+                _ => None,
+            },
+            orientation: Some(uint(Tag::Orientation).filter(|orientation| (1..=8).contains(orientation)).unwrap_or(NORMAL_ORIENTATION as u32) as u16),
+            latitude: coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, "S").filter(|latitude| (-90.0..=90.0).contains(latitude)),
+            longitude: coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef, "W").filter(|longitude| (-180.0..=180.0).contains(longitude)),
+            altitude: rationals(Tag::GPSAltitude).first()
+                .filter(|altitude| altitude.is_finite())
+                .map(|altitude| if uint(Tag::GPSAltitudeRef) == Some(1) { -altitude } else { *altitude }),
+        }
+    }
+
+    /// latitude and longitude of the picture, if it has both
+    pub fn location(&self) -> Option<(f64, f64)> {
+        self.latitude.zip(self.longitude)
+    }
+
+    /// the camera, lens and exposure, as shown in the picture info
+    pub fn display(&self) -> String {
+        let mut lines: Vec<String> = vec![];
+        if let Some(taken) = self.taken.and_then(|taken| chrono::DateTime::from_timestamp(taken, 0)) {
+            lines.push(format!("taken: {}", taken.format("%Y-%m-%d %H:%M:%S")))
+        };
+        // models often repeat the make, as in "Canon PowerShot G3"
+        let camera = match (&self.make, &self.model) {
+            (Some(make), Some(model)) if model.starts_with(make.as_str()) => model.clone(),
+            (make, model) => [make, model].into_iter().flatten().cloned().collect::<Vec<String>>().join(" "),
+        };
+        if !camera.is_empty() {
+            lines.push(format!("camera: {}", camera))
+        };
+        if let Some(lens) = &self.lens {
+            lines.push(format!("lens: {}", lens))
+        };
+        let mut exposure: Vec<String> = vec![];
+        if let Some(focal_length) = self.focal_length { exposure.push(format!("{} mm", focal_length)) };
+        if let Some(time) = &self.exposure { exposure.push(time.clone()) };
+        if let Some(iso) = self.iso { exposure.push(format!("ISO {}", iso)) };
+        if !exposure.is_empty() {
+            lines.push(format!("exposure: {}", exposure.join(", ")))
+        };
+        if let Some((latitude, longitude)) = self.location() {
+            lines.push(match self.altitude {
+                Some(altitude) => format!("location: {:.6}, {:.6}, {:.0} m", latitude, longitude, altitude),
+                None => format!("location: {:.6}, {:.6}", latitude, longitude),
+            })
+        };
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pictures_without_exif_have_the_normal_orientation_only() {
+        let exif_data = ExifData::from_file("testdata/nature/flower.jpg");
+        assert_eq!(ExifData { orientation: Some(NORMAL_ORIENTATION), ..ExifData::default() }, exif_data);
+        assert_eq!("", exif_data.display());
+    }
+
+    #[test]
+    fn exif_fields_are_shown_when_present() {
+        let exif_data = ExifData {
+            taken: Some(1704103957),
+            make: Some(String::from("Canon")),
+            model: Some(String::from("EOS R6")),
+            lens: None,
+            exposure: Some(String::from("1/250 s")),
+            iso: Some(400),
+            focal_length: Some(35.0),
+            orientation: Some(6),
+            latitude: None,
+            longitude: None,
+            altitude: None,
+        };
+        assert_eq!("taken: 2024-01-01 10:12:37\ncamera: Canon EOS R6\nexposure: 35 mm, 1/250 s, ISO 400", exif_data.display());
+        let located = ExifData { latitude: Some(48.858093), longitude: Some(-2.294694), altitude: Some(35.4), ..ExifData::default() };
+        assert_eq!("location: 48.858093, -2.294694, 35 m", located.display());
+        assert_eq!(None, ExifData { latitude: Some(48.858093), ..ExifData::default() }.location());
+    }
+
+    #[test]
+    fn exif_fields_are_read_from_the_picture_file() {
+        let exif_data = ExifData::from_file("testdata/nature/rose.jpeg");
+        assert_eq!(Some(String::from("Canon")), exif_data.make);
+        assert_eq!(Some(String::from("Canon PowerShot G3")), exif_data.model);
+        assert_eq!(Some(50), exif_data.iso);
+        assert_eq!(Some(NORMAL_ORIENTATION), exif_data.orientation);
+        assert_eq!("taken: 2006-02-14 15:39:48\ncamera: Canon PowerShot G3\nexposure: 28.8 mm, 1 s, ISO 50", exif_data.display());
+    }
+}
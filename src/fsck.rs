@@ -0,0 +1,161 @@
+use crate::library::{CoverRecord, TagRecord};
+use crate::path::replace_home_with_tilde;
+use std::collections::HashSet;
+
+/// size in bytes of a palette blob: 9 colors of 4 bytes
+pub const PALETTE_BLOB_SIZE: usize = 36;
+
+/// a row of the Picture table, as far as the integrity checks are concerned
+#[derive(Clone, Debug)]
+pub struct StoredPicture {
+    pub file_path: String,
+    pub cover: bool,
+    pub palette_size: usize,
+}
+
+/// a table whose paths are checked
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Table {
+    Picture, Tag, Cover,
+}
+
+impl std::fmt::Display for Table {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// an inconsistency between the rows of the database
+#[derive(PartialEq, Clone, Debug)]
+pub enum Issue {
+    /// a picture stored twice, under its path with ~ and under its path with the home directory
+    DuplicatePath(String),
+    /// a path of the table stored with the home directory instead of ~
+    AbsolutePath(Table, String),
+    /// a tag (file path, label) of a picture that is not in the Picture table
+    OrphanTag(String, String),
+    /// a cover (directory, file name) of a picture that is not in the Picture table
+    OrphanCover(String, String),
+    /// a picture marked as cover that has no row in the Cover table
+    CoverNotInTable(String),
+    /// a picture in the Cover table that is not marked as cover
+    CoverNotInColumn(String),
+    /// a picture whose palette blob has not the expected size
+    BadPalette(String, usize),
+    /// rows of the full text index that don't match the Picture table
+    SearchIndex(usize),
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Issue::DuplicatePath(file_path) => write!(f, "picture {} stored twice, with ~ and with the home directory", file_path),
+            Issue::AbsolutePath(table, path) => write!(f, "{} path {} stored without ~", table, path),
+            Issue::OrphanTag(file_path, label) => write!(f, "tag {} of {} which is not in the database", label, file_path),
+            Issue::OrphanCover(dir_path, file_name) => write!(f, "cover {}/{} which is not in the database", dir_path, file_name),
+            Issue::CoverNotInTable(file_path) => write!(f, "picture {} marked as cover but not in the Cover table", file_path),
+            Issue::CoverNotInColumn(file_path) => write!(f, "picture {} in the Cover table but not marked as cover", file_path),
+            Issue::BadPalette(file_path, size) => write!(f, "picture {} has a palette of {} bytes instead of {}", file_path, size, PALETTE_BLOB_SIZE),
+            Issue::SearchIndex(count) => write!(f, "{} rows of the full text index don't match the pictures", count),
+        }
+    }
+}
+
+/// the inconsistencies between the Picture, Tag and Cover rows; paths are compared in their ~ form
+pub fn issues(pictures: &[StoredPicture], tags: &[TagRecord], covers: &[CoverRecord]) -> Vec<Issue> {
+    let mut result: Vec<Issue> = vec![];
+    let mut file_paths: HashSet<String> = HashSet::new();
+    let mut cover_file_paths: HashSet<String> = HashSet::new();
+    for picture in pictures {
+        let file_path = replace_home_with_tilde(&picture.file_path);
+        if file_path != picture.file_path {
+            result.push(Issue::AbsolutePath(Table::Picture, picture.file_path.clone()))
+        };
+        if !file_paths.insert(file_path.clone()) {
+            result.push(Issue::DuplicatePath(file_path.clone()))
+        };
+        if picture.cover {
+            cover_file_paths.insert(file_path);
+        };
+        if picture.palette_size != PALETTE_BLOB_SIZE {
+            result.push(Issue::BadPalette(picture.file_path.clone(), picture.palette_size))
+        };
+    };
+    let mut absolute_tag_paths: HashSet<String> = HashSet::new();
+    for tag in tags {
+        let file_path = replace_home_with_tilde(&tag.file_path);
+        if file_path != tag.file_path && absolute_tag_paths.insert(tag.file_path.clone()) {
+            result.push(Issue::AbsolutePath(Table::Tag, tag.file_path.clone()))
+        };
+        if !file_paths.contains(&file_path) {
+            result.push(Issue::OrphanTag(tag.file_path.clone(), tag.label.clone()))
+        };
+    };
+    let mut absolute_cover_paths: HashSet<String> = HashSet::new();
+    let mut covered_file_paths: HashSet<String> = HashSet::new();
+    for cover in covers {
+        let dir_path = replace_home_with_tilde(&cover.dir_path);
+        if dir_path != cover.dir_path && absolute_cover_paths.insert(cover.dir_path.clone()) {
+            result.push(Issue::AbsolutePath(Table::Cover, cover.dir_path.clone()))
+        };
+        let file_path = format!("{}/{}", dir_path, cover.file_name);
+        if !file_paths.contains(&file_path) {
+            result.push(Issue::OrphanCover(cover.dir_path.clone(), cover.file_name.clone()))
+        } else if !cover_file_paths.contains(&file_path) {
+            result.push(Issue::CoverNotInColumn(file_path.clone()))
+        };
+        covered_file_paths.insert(file_path);
+    };
+    for picture in pictures {
+        let file_path = replace_home_with_tilde(&picture.file_path);
+        if picture.cover && !covered_file_paths.contains(&file_path) {
+            result.push(Issue::CoverNotInTable(file_path))
+        }
+    };
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn home() -> String {
+        std::env::home_dir().unwrap().display().to_string()
+    }
+
+    fn picture(file_path: &str, cover: bool) -> StoredPicture {
+        StoredPicture { file_path: file_path.to_string(), cover, palette_size: PALETTE_BLOB_SIZE }
+    }
+
+    #[test]
+    fn consistent_rows_have_no_issue() {
+        let pictures = vec![picture("~/photos/sunset.jpg", true), picture("/photos/dawn.jpg", false)];
+        let tags = vec![TagRecord { file_path: String::from("~/photos/sunset.jpg"), label: String::from("sea") }];
+        let covers = vec![CoverRecord { dir_path: String::from("~/photos"), file_name: String::from("sunset.jpg"), rank: 1 }];
+        assert!(issues(&pictures, &tags, &covers).is_empty());
+    }
+
+    #[test]
+    fn redundant_rows_that_drifted_are_reported() {
+        let absolute = format!("{}/photos/sunset.jpg", home());
+        let mut bad_palette = picture("~/photos/dawn.jpg", true);
+        bad_palette.palette_size = 12;
+        let pictures = vec![picture("~/photos/sunset.jpg", false), picture(&absolute, false), bad_palette];
+        let tags = vec![
+            TagRecord { file_path: absolute.clone(), label: String::from("sea") },
+            TagRecord { file_path: String::from("~/photos/gone.jpg"), label: String::from("sea") }];
+        let covers = vec![
+            CoverRecord { dir_path: String::from("~/photos"), file_name: String::from("sunset.jpg"), rank: 1 },
+            CoverRecord { dir_path: String::from("~/photos"), file_name: String::from("gone.jpg"), rank: 1 }];
+        assert_eq!(vec![
+            Issue::AbsolutePath(Table::Picture, absolute.clone()),
+            Issue::DuplicatePath(String::from("~/photos/sunset.jpg")),
+            Issue::BadPalette(String::from("~/photos/dawn.jpg"), 12),
+            Issue::AbsolutePath(Table::Tag, absolute),
+            Issue::OrphanTag(String::from("~/photos/gone.jpg"), String::from("sea")),
+            Issue::CoverNotInColumn(String::from("~/photos/sunset.jpg")),
+            Issue::OrphanCover(String::from("~/photos"), String::from("gone.jpg")),
+            Issue::CoverNotInTable(String::from("~/photos/dawn.jpg")),
+        ], issues(&pictures, &tags, &covers));
+    }
+}
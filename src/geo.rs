@@ -0,0 +1,65 @@
+use anyhow::{anyhow, Result};
+
+/// mean radius of the Earth in kilometers
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// length of a degree of latitude in kilometers
+pub const KM_PER_DEGREE: f64 = EARTH_RADIUS_KM * std::f64::consts::PI / 180.0;
+
+/// great circle distance in kilometers between two points given as latitude and longitude in degrees
+pub fn distance_km(latitude_a: f64, longitude_a: f64, latitude_b: f64, longitude_b: f64) -> f64 {
+    let (phi_a, phi_b) = (latitude_a.to_radians(), latitude_b.to_radians());
+    let delta_phi = (latitude_b - latitude_a).to_radians();
+    let delta_lambda = (longitude_b - longitude_a).to_radians();
+    let a = (delta_phi / 2.0).sin().powi(2) + phi_a.cos() * phi_b.cos() * (delta_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+}
+
+/// the comma separated numbers of source, e.g. 48.8584,2.2945,5 for near:, checking their count
+pub fn parse_numbers(source: &str, count: usize, what: &str) -> Result<Vec<f64>> {
+    let numbers: Vec<f64> = source.split(',')
+        .map(|number| number.trim().parse::<f64>())
+        .collect::<std::result::Result<Vec<f64>, _>>()
+        .map_err(|_| anyhow!(format!("{} should be {} comma separated numbers: {}", what, count, source)))?;
+    if numbers.len() != count || numbers.iter().any(|number| !number.is_finite()) {
+        return Err(anyhow!(format!("{} should be {} comma separated numbers: {}", what, count, source)))
+    };
+    Ok(numbers)
+}
+
+/// a point given as LAT,LON in degrees
+pub fn parse_point(source: &str) -> Result<(f64, f64)> {
+    let numbers = parse_numbers(source, 2, "a point")?;
+    checked_point(numbers[0], numbers[1], source)
+}
+
+/// the latitude and longitude if they are in range
+pub fn checked_point(latitude: f64, longitude: f64, source: &str) -> Result<(f64, f64)> {
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return Err(anyhow!(format!("latitude should be between -90 and 90 and longitude between -180 and 180: {}", source)))
+    };
+    Ok((latitude, longitude))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances_between_points_on_earth() {
+        let (paris, london) = ((48.8566, 2.3522), (51.5074, -0.1278));
+        let distance = distance_km(paris.0, paris.1, london.0, london.1);
+        assert!((distance - 343.5).abs() < 1.0, "{}", distance);
+        assert_eq!(0.0, distance_km(paris.0, paris.1, paris.0, paris.1));
+        assert!((distance_km(0.0, 179.5, 0.0, -179.5) - KM_PER_DEGREE).abs() < 0.001);
+    }
+
+    #[test]
+    fn parsing_points() {
+        assert_eq!((48.8584, -2.5), parse_point("48.8584, -2.5").unwrap());
+        assert!(parse_point("48.8584").is_err());
+        assert!(parse_point("91,0").is_err());
+        assert!(parse_point("north,east").is_err());
+        assert_eq!(vec![1.0, 2.0, 5.0], parse_numbers("1,2,5", 3, "near").unwrap());
+    }
+}
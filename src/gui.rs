@@ -4,7 +4,7 @@ use crate::glib::ControlFlow;
 use crate::glib::ControlFlow::Continue;
 use crate::display::title_display;
 use crate::editor::{Editor,InputKind};
-use crate::glib::timeout_add_local;
+use crate::glib::{timeout_add_local, timeout_add_local_once};
 use anyhow::{Result};
 use crate::commands::{Command,Shortcuts, export_shortcuts};
 use std::time::Duration;
@@ -17,12 +17,28 @@ use crate::Args;
 use crate::order;
 use crate::Catalog;
 use crate::picture_entry::PictureEntry;
-use crate::picture_io::check_or_create_thumbnail_file;
+use crate::exif_data::NORMAL_ORIENTATION;
+use crate::picture_io::{check_or_create_thumbnail_file, oriented_image, picture_format, read_frames};
+use image::{ImageFormat, RgbaImage};
 use gtk::prelude::*;
 use std::rc::Rc;
 use std::cell::RefCell;
 use gtk::glib::clone;
 
+// radius in kilometers proposed when showing the pictures near the current one
+const DEFAULT_NEAR_RADIUS: &str = "5";
+
+// the frames of the animated picture shown in the single view, and where its playback is
+#[derive(Default)]
+struct Animation {
+    file_path: String,
+    frames: Vec<(gdk::MemoryTexture, Duration)>,
+    index: usize,
+    paused: bool,
+    // changed whenever playback stops or restarts, so that the frame timer of a former playback ends
+    generation: u64,
+}
+
 struct Gui {
     application_window: gtk::ApplicationWindow,
     single_view_scrolled_window: gtk::ScrolledWindow,
@@ -34,6 +50,7 @@ struct Gui {
     cells_per_row: i32,
     shortcuts: Shortcuts,
     editor: Editor,
+    animation: Rc<RefCell<Animation>>,
 }
 
 impl Gui {
@@ -164,6 +181,7 @@ pub fn build_gui(application: &gtk::Application, args: &Args, catalog_rc: &Rc<Re
         cells_per_row,
         shortcuts: shortcuts.clone(),
         editor: Editor::new(),
+        animation: Rc::new(RefCell::new(Animation::default())),
     };
     let gui_rc = Rc::new(RefCell::new(gui));
 
@@ -285,6 +303,7 @@ fn refresh_view(gui: &Gui, catalog: &Catalog) {
         if child == gui.single_view_scrolled_window {
             set_picture_for_single_view(gui, catalog)
         } else {
+            stop_animation(gui);
             set_all_pictures_for_multiple_view(gui, catalog)
         }
     };
@@ -418,13 +437,35 @@ fn view_mode_process_key(key: Key, gui: &mut Gui, catalog: &mut Catalog) -> bool
                         Command::Search => {
                             gui.editor.begin_input(InputKind::Search, catalog.tags.clone());
                         }
+                        Command::NextMatch => catalog.move_to_next_match(true),
+                        Command::PrevMatch => catalog.move_to_next_match(false),
+                        Command::AddToAlbum => {
+                            gui.editor.begin_input(InputKind::Album, catalog.album_names());
+                        }
+                        Command::MoveLeft => result = catalog.move_current_entry_in_album(false),
+                        Command::MoveRight => result = catalog.move_current_entry_in_album(true),
+                        Command::SavedSearch => {
+                            gui.editor.begin_input(InputKind::SavedSearch, catalog.saved_search_names());
+                        }
                         Command::Uncover => result = catalog.uncover_current_entry(),
+                        Command::Undo => result = catalog.undo(),
+                        Command::Redo => result = catalog.redo(),
                         Command::UnSelectPage => result = catalog.unselect_page(),
                         Command::UnselectAll => result = catalog.unselect_all(),
                         Command::TogglePalette => {
                             catalog.toggle_palette();
                             set_title(gui, catalog);
                         },
+                        Command::TogglePlay => {
+                            refresh = false;
+                            toggle_play(gui);
+                            set_title(gui, catalog);
+                        },
+                        Command::NextFrame => {
+                            refresh = false;
+                            show_next_frame(gui);
+                            set_title(gui, catalog);
+                        },
                         Command::StartPosition => catalog.mut_navigator().move_to_first_index(),
                         Command::EndPosition => catalog.mut_navigator().move_to_last_index(),
                         Command::Next => catalog.mut_navigator().move_next_page(),
@@ -446,7 +487,7 @@ fn view_mode_process_key(key: Key, gui: &mut Gui, catalog: &mut Catalog) -> bool
                             catalog.count_selected()
                         },
                         Command::AddTag => {
-                            gui.editor.begin_input(InputKind::AddTag, catalog.tags.clone());
+                            gui.editor.begin_input(InputKind::AddTag, catalog.tag_names());
                         }
                         Command::DeleteTag => {
                             gui.editor.begin_input(InputKind::DeleteTag, catalog.current_entry().unwrap().image_data.tags.clone());
@@ -457,6 +498,16 @@ fn view_mode_process_key(key: Key, gui: &mut Gui, catalog: &mut Catalog) -> bool
                         Command::Relabel => {
                             gui.editor.begin_input(InputKind::Relabel, catalog.tags.clone());
                         }
+                        Command::RenameLabel => {
+                            gui.editor.begin_input(InputKind::RenameLabel, catalog.tags.clone());
+                        }
+                        Command::Near => {
+                            gui.editor.begin_input_with_text(InputKind::Radius, DEFAULT_NEAR_RADIUS);
+                        },
+                        Command::Note => if let Some(entry) = catalog.current_entry() {
+                            let note = entry.image_data.note.clone();
+                            gui.editor.begin_input_with_text(InputKind::Note, &note);
+                        },
                         Command::Right => {
                             refresh = arrow_command(Direction::Right, gui, catalog)
                         },
@@ -510,7 +561,13 @@ fn draw_palette(ctx: &Context, width: i32, height: i32, colors: &[u32;9]) {
     ctx.paint().expect("can't paint surface")
 }
 fn set_title(gui: &Gui, catalog: &Catalog) {
-    gui.application_window.set_title(Some(&title_display(catalog, &gui.editor)))
+    let animation = gui.animation.borrow();
+    let position = if animation.paused && !animation.frames.is_empty() {
+        format!(" ⏸{}/{}", animation.index + 1, animation.frames.len())
+    } else {
+        String::new()
+    };
+    gui.application_window.set_title(Some(&format!("{}{}", title_display(catalog, &gui.editor), position)))
 }
 
 fn arrow_command_full_size(direction: Direction, gui: &Gui) -> bool {
@@ -540,7 +597,11 @@ fn set_picture_for_single_view(gui: &Gui, catalog: &Catalog) {
     };
     picture.set_opacity(opacity);
     picture.set_can_shrink(!catalog.full_size_on());
-    picture.set_filename(Some(entry.original_file_path()));
+    let file_path = entry.original_file_path();
+    if !(entry.is_animated() && show_animation(gui, &file_path, entry.exif.orientation)) {
+        stop_animation(gui);
+        set_picture_file(picture, &file_path, entry.exif.orientation);
+    }
     if let Some(widget) = view_box.last_child() {
         if widget != *picture {
             view_box.remove(&widget)
@@ -553,6 +614,98 @@ fn set_picture_for_single_view(gui: &Gui, catalog: &Catalog) {
     }
 }
 
+// show the current frame of an animated picture in the single view, reading its frames and starting
+// the playback when the picture is not the one already playing; false if its frames can't be read
+fn show_animation(gui: &Gui, file_path: &str, orientation: Option<u16>) -> bool {
+    let start = {
+        let mut animation = gui.animation.borrow_mut();
+        let start = animation.file_path != file_path;
+        if start {
+            match read_frames(file_path, orientation.unwrap_or(NORMAL_ORIENTATION)) {
+                Ok(frames) if !frames.is_empty() => {
+                    animation.file_path = file_path.to_string();
+                    animation.frames = frames.into_iter().map(|(image, delay)| (memory_texture(image), delay)).collect();
+                    animation.index = 0;
+                    animation.paused = false;
+                    animation.generation += 1;
+                },
+                Ok(_) => return false,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    return false
+                },
+            }
+        };
+        gui.single_view_picture.set_paintable(Some(&animation.frames[animation.index].0));
+        start
+    };
+    if start {
+        play_next_frame(&gui.single_view_picture, &gui.animation)
+    };
+    true
+}
+
+fn stop_animation(gui: &Gui) {
+    let mut animation = gui.animation.borrow_mut();
+    if !animation.frames.is_empty() {
+        let generation = animation.generation + 1;
+        *animation = Animation { generation, ..Animation::default() }
+    }
+}
+
+// show the next frame once the current one has been shown for its delay, and so on until the
+// playback is paused, stopped or restarted
+fn play_next_frame(picture: &gtk::Picture, animation_rc: &Rc<RefCell<Animation>>) {
+    let (delay, generation) = {
+        let animation = animation_rc.borrow();
+        (animation.frames[animation.index].1, animation.generation)
+    };
+    timeout_add_local_once(delay, clone!(#[strong] picture, #[strong] animation_rc, move || {
+        // the borrow is released before the next frame is scheduled
+        let playing = match animation_rc.try_borrow_mut() {
+            Ok(mut animation) if animation.generation == generation && !animation.paused && !animation.frames.is_empty() => {
+                animation.index = (animation.index + 1) % animation.frames.len();
+                picture.set_paintable(Some(&animation.frames[animation.index].0));
+                true
+            },
+            _ => false,
+        };
+        if playing {
+            play_next_frame(&picture, &animation_rc)
+        }
+    }));
+}
+
+fn toggle_play(gui: &Gui) {
+    let resume = {
+        let mut animation = gui.animation.borrow_mut();
+        if animation.frames.is_empty() {
+            return
+        };
+        animation.paused = !animation.paused;
+        animation.generation += 1;
+        !animation.paused
+    };
+    if resume {
+        play_next_frame(&gui.single_view_picture, &gui.animation)
+    }
+}
+
+// pause the playback and step to the next frame
+fn show_next_frame(gui: &Gui) {
+    let mut animation = gui.animation.borrow_mut();
+    if animation.frames.is_empty() {
+        return
+    };
+    if !animation.paused {
+        animation.paused = true;
+        animation.generation += 1;
+    } else {
+        animation.index = (animation.index + 1) % animation.frames.len();
+    };
+    gui.single_view_picture.set_paintable(Some(&animation.frames[animation.index].0));
+}
+
 fn set_picture_for_cell_at(gui: &Gui, catalog: &Catalog, col: usize, row: usize) {
     let widget = gui.multiple_view_grid.child_at(col as i32, row as i32).expect("cannot find cell box in multiple view grid");
     let cell_box = widget.downcast::<gtk::Box>().expect("cannot downcast widget to Box");
@@ -692,15 +845,48 @@ fn picture_for_entry(entry: &PictureEntry, catalog: &Catalog) -> gtk::Picture {
     picture.set_halign(Align::Center);
     picture.set_opacity(opacity);
     picture.set_can_shrink(!catalog.full_size_on());
-    if catalog.navigator().cells_per_row() < 10 {
-        picture.set_filename(Some(entry.original_file_path()));
+    // originals that must be decoded to be shown upright are too slow to load in a grid: their thumbnail is turned instead
+    if catalog.navigator().cells_per_row() < 10 && !needs_decoding(&entry.original_file_path(), entry.exif.orientation) {
+        set_picture_file(&picture, &entry.original_file_path(), entry.exif.orientation);
     } else {
         let _ = check_or_create_thumbnail_file(&entry.thumbnail_file_path(), &entry.original_file_path());
-        picture.set_filename(Some(entry.thumbnail_file_path()));
+        set_picture_file(&picture, &entry.thumbnail_file_path(), entry.exif.orientation);
     };
     picture.set_visible(true);
     picture
 }
+
+// show the picture file upright: a file with an EXIF orientation other than the normal one, or in
+// a format gtk doesn't load by itself, is decoded and turned before display, falling back on the
+// file as stored if it can't be decoded
+fn set_picture_file(picture: &gtk::Picture, file_path: &str, orientation: Option<u16>) {
+    if needs_decoding(file_path, orientation) {
+        match oriented_image(file_path, orientation.unwrap_or(NORMAL_ORIENTATION)) {
+            Ok(image) => {
+                picture.set_paintable(Some(&memory_texture(image)));
+                return
+            },
+            Err(err) => eprintln!("{}", err),
+        }
+    };
+    picture.set_filename(Some(file_path));
+}
+
+fn needs_decoding(file_path: &str, orientation: Option<u16>) -> bool {
+    let loaded_by_gtk = matches!(picture_format(file_path), Some(ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::Tiff));
+    orientation.unwrap_or(NORMAL_ORIENTATION) != NORMAL_ORIENTATION || !loaded_by_gtk
+}
+
+fn memory_texture(image: RgbaImage) -> gdk::MemoryTexture {
+    let (width, height) = image.dimensions();
+    gdk::MemoryTexture::new(
+        width as i32,
+        height as i32,
+        gdk::MemoryFormat::R8g8b8a8,
+        &gtk::glib::Bytes::from_owned(image.into_raw()),
+        width as usize * 4)
+}
+
 fn label_for_entry(entry: &PictureEntry, with_focus: bool) -> gtk::Label {
     let label = gtk::Label::new(Some(&entry.label_display(with_focus)));
     label.set_valign(Align::Center);
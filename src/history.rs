@@ -0,0 +1,38 @@
+use crate::comment::Comment;
+use crate::image_data::ImageData;
+use crate::picture_entry::PictureEntry;
+
+/// A comment applied to a picture entry, with the picture data before and after,
+/// so that it can be undone and redone. Comments applied at once, e.g. on a range
+/// of pictures, are recorded in the same batch and undone together.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub file_path: String,
+    pub comment: String,
+    pub before: ImageData,
+    pub after: ImageData,
+    pub deleted_before: bool,
+    pub deleted_after: bool,
+}
+
+impl HistoryEntry {
+    pub fn new(comment: &Comment, before: &PictureEntry, after: &PictureEntry) -> Self {
+        HistoryEntry {
+            file_path: after.file_path.clone(),
+            comment: comment.to_string(),
+            before: before.image_data.clone(),
+            after: after.image_data.clone(),
+            deleted_before: before.deleted,
+            deleted_after: after.deleted,
+        }
+    }
+
+    /// the picture data and deleted status to restore, when undoing or redoing
+    pub fn restored(&self, undo: bool) -> (ImageData, bool) {
+        if undo {
+            (self.before.clone(), self.deleted_before)
+        } else {
+            (self.after.clone(), self.deleted_after)
+        }
+    }
+}
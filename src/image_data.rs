@@ -1,10 +1,11 @@
 use std::collections::HashSet;
 use crate::palette::Palette;
+use crate::perceptual::PerceptualHash;
 use serde::{Deserialize, Serialize};
 use crate::rank::Rank;
 
 
-#[derive(PartialEq, Clone, Debug, Deserialize, Serialize)]
+#[derive(PartialEq, Clone, Debug, Default, Deserialize, Serialize)]
 pub struct ImageData {
     pub colors: usize,
     pub rank: Rank,
@@ -13,4 +14,8 @@ pub struct ImageData {
     pub label: String,
     pub cover: bool,
     pub tags: HashSet<String>,
+    #[serde(default)]
+    pub note: String,
+    #[serde(default)]
+    pub perceptual_hash: Option<PerceptualHash>,
 }
@@ -0,0 +1,94 @@
+use clap::builder::PossibleValue;
+use crate::path::{is_prefix_path, standard_directory};
+use crate::picture_entry::PictureEntry;
+
+/// which copy of a group of duplicate pictures is kept when deduplicating
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum KeepPolicy {
+    HighestRank, Oldest, ShortestPath, InStandardDir,
+}
+
+impl KeepPolicy {
+    /// index of the entry to keep among duplicates, ties going to the shortest then first path
+    pub fn survivor(&self, entries: &[PictureEntry]) -> usize {
+        let standard_dir = standard_directory();
+        let mut indices: Vec<usize> = (0..entries.len()).collect();
+        indices.sort_by(|&a, &b| {
+            let (entry_a, entry_b) = (&entries[a], &entries[b]);
+            let policy_order = match self {
+                KeepPolicy::HighestRank => (entry_a.image_data.rank as i64).cmp(&(entry_b.image_data.rank as i64))
+                    .then(entry_b.image_data.cover.cmp(&entry_a.image_data.cover)),
+                KeepPolicy::Oldest => entry_a.modified_time.cmp(&entry_b.modified_time),
+                KeepPolicy::ShortestPath => std::cmp::Ordering::Equal,
+                KeepPolicy::InStandardDir => {
+                    let in_dir_a = !standard_dir.is_empty() && is_prefix_path(&standard_dir, &entry_a.file_path);
+                    let in_dir_b = !standard_dir.is_empty() && is_prefix_path(&standard_dir, &entry_b.file_path);
+                    in_dir_b.cmp(&in_dir_a)
+                },
+            };
+            policy_order
+                .then(entry_a.file_path.len().cmp(&entry_b.file_path.len()))
+                .then(entry_a.file_path.cmp(&entry_b.file_path))
+        });
+        indices[0]
+    }
+}
+
+impl std::fmt::Display for KeepPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match clap::ValueEnum::to_possible_value(self) {
+            Some(value) => write!(f, "{}", value.get_name()),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl clap::ValueEnum for KeepPolicy {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[KeepPolicy::HighestRank, KeepPolicy::Oldest, KeepPolicy::ShortestPath, KeepPolicy::InStandardDir]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            KeepPolicy::HighestRank => PossibleValue::new("highest-rank").help("this is default"),
+            KeepPolicy::Oldest => PossibleValue::new("oldest"),
+            KeepPolicy::ShortestPath => PossibleValue::new("shortest-path"),
+            KeepPolicy::InStandardDir => PossibleValue::new("in-standard-dir"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_data::ImageData;
+    use crate::picture_entry::make_picture_entry;
+    use crate::rank::Rank;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn my_entry(file_path: &str, rank: Rank, day: u64) -> PictureEntry {
+        make_picture_entry(file_path.to_string(), 100, UNIX_EPOCH + Duration::from_secs(day * 86400), ImageData { rank, ..Default::default() }, false)
+    }
+
+    #[test]
+    fn survivor_depends_on_the_keep_policy() {
+        let entries = vec![
+            my_entry("/photos/2024/beach/copy of sunset.jpg", Rank::ThreeStars, 20),
+            my_entry("/photos/sunset.jpg", Rank::OneStar, 30),
+            my_entry("/backup/old/sunset.jpg", Rank::NoStar, 10),
+        ];
+        assert_eq!(0, KeepPolicy::HighestRank.survivor(&entries));
+        assert_eq!(2, KeepPolicy::Oldest.survivor(&entries));
+        assert_eq!(1, KeepPolicy::ShortestPath.survivor(&entries));
+    }
+
+    #[test]
+    fn ties_go_to_the_shortest_path() {
+        let entries = vec![
+            my_entry("/photos/2024/sunset.jpg", Rank::TwoStars, 10),
+            my_entry("/photos/sunset.jpg", Rank::TwoStars, 10),
+        ];
+        assert_eq!(1, KeepPolicy::HighestRank.survivor(&entries));
+        assert_eq!(1, KeepPolicy::Oldest.survivor(&entries));
+    }
+}
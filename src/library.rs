@@ -0,0 +1,415 @@
+use anyhow::{anyhow, Result};
+use clap::builder::PossibleValue;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+
+/// format of the files the library is exported to or imported from
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Format {
+    Json, Csv,
+}
+
+impl Format {
+    /// the format of a file, from its extension, json being the default
+    pub fn of_file(file_path: &str) -> Format {
+        match Path::new(file_path).extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => Format::Csv,
+            _ => Format::Json,
+        }
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Json => write!(f, "json"),
+            Format::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+impl clap::ValueEnum for Format {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Format::Json, Format::Csv]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            Format::Json => PossibleValue::new("json").help("this is default"),
+            Format::Csv => PossibleValue::new("csv"),
+        })
+    }
+}
+
+/// a row of the Picture table, paths being kept as stored, with ~ for the home directory
+#[derive(PartialEq, Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PictureRecord {
+    pub file_path: String,
+    pub file_size: i64,
+    pub colors: i64,
+    pub modified_time: i64,
+    pub rank: i64,
+    pub palette: String,
+    pub label: String,
+    pub selected: bool,
+    pub deleted: bool,
+    pub cover: bool,
+    pub content_hash: Option<String>,
+    pub perceptual_hash: Option<i64>,
+    #[serde(default)]
+    pub note: String,
+    #[serde(default)]
+    pub updated_time: Option<i64>,
+    #[serde(default)]
+    pub taken_time: Option<i64>,
+    #[serde(default)]
+    pub camera_make: Option<String>,
+    #[serde(default)]
+    pub camera_model: Option<String>,
+    #[serde(default)]
+    pub lens: Option<String>,
+    #[serde(default)]
+    pub exposure: Option<String>,
+    #[serde(default)]
+    pub iso: Option<i64>,
+    #[serde(default)]
+    pub focal_length: Option<f64>,
+    #[serde(default)]
+    pub orientation: Option<i64>,
+    #[serde(default)]
+    pub latitude: Option<f64>,
+    #[serde(default)]
+    pub longitude: Option<f64>,
+    #[serde(default)]
+    pub altitude: Option<f64>,
+    #[serde(default)]
+    pub gps_read: bool,
+    #[serde(default)]
+    pub width: Option<i64>,
+    #[serde(default)]
+    pub height: Option<i64>,
+    #[serde(default)]
+    pub frames: Option<i64>,
+}
+
+/// a row of the Tag table
+#[derive(PartialEq, Clone, Debug, Deserialize, Serialize)]
+pub struct TagRecord {
+    pub file_path: String,
+    pub label: String,
+}
+
+/// a row of the Cover table
+#[derive(PartialEq, Clone, Debug, Deserialize, Serialize)]
+pub struct CoverRecord {
+    pub dir_path: String,
+    pub file_name: String,
+    pub rank: i64,
+}
+
+/// the content of the database tables describing the pictures
+#[derive(PartialEq, Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Library {
+    pub pictures: Vec<PictureRecord>,
+    pub tags: Vec<TagRecord>,
+    pub covers: Vec<CoverRecord>,
+}
+
+/// what an import did: rows inserted per table, and rows of the file that were in conflict with the database and left aside
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub pictures: usize,
+    pub tags: usize,
+    pub covers: usize,
+    pub conflicts: Vec<String>,
+}
+
+// the three tables in a single csv file, the table column telling which table a row belongs to
+#[derive(Debug, Deserialize, Serialize)]
+struct CsvRecord {
+    table: String,
+    file_path: Option<String>,
+    file_size: Option<i64>,
+    colors: Option<i64>,
+    modified_time: Option<i64>,
+    rank: Option<i64>,
+    palette: Option<String>,
+    label: Option<String>,
+    selected: Option<bool>,
+    deleted: Option<bool>,
+    cover: Option<bool>,
+    content_hash: Option<String>,
+    perceptual_hash: Option<i64>,
+    #[serde(default)]
+    note: Option<String>,
+    #[serde(default)]
+    updated_time: Option<i64>,
+    #[serde(default)]
+    taken_time: Option<i64>,
+    #[serde(default)]
+    camera_make: Option<String>,
+    #[serde(default)]
+    camera_model: Option<String>,
+    #[serde(default)]
+    lens: Option<String>,
+    #[serde(default)]
+    exposure: Option<String>,
+    #[serde(default)]
+    iso: Option<i64>,
+    #[serde(default)]
+    focal_length: Option<f64>,
+    #[serde(default)]
+    orientation: Option<i64>,
+    #[serde(default)]
+    latitude: Option<f64>,
+    #[serde(default)]
+    longitude: Option<f64>,
+    #[serde(default)]
+    altitude: Option<f64>,
+    #[serde(default)]
+    gps_read: Option<bool>,
+    #[serde(default)]
+    width: Option<i64>,
+    #[serde(default)]
+    height: Option<i64>,
+    #[serde(default)]
+    frames: Option<i64>,
+    dir_path: Option<String>,
+    file_name: Option<String>,
+}
+
+impl CsvRecord {
+    fn empty(table: &str) -> Self {
+        CsvRecord {
+            table: table.to_string(),
+            file_path: None, file_size: None, colors: None, modified_time: None, rank: None,
+            palette: None, label: None, selected: None, deleted: None, cover: None,
+            content_hash: None, perceptual_hash: None, note: None, updated_time: None,
+            taken_time: None, camera_make: None, camera_model: None, lens: None, exposure: None, iso: None, focal_length: None, orientation: None,
+            latitude: None, longitude: None, altitude: None, gps_read: None,
+            width: None, height: None,
+            frames: None,
+            dir_path: None, file_name: None,
+        }
+    }
+}
+
+fn missing(table: &str, column: &str) -> anyhow::Error {
+    anyhow!(format!("csv row of table {} without {}", table, column))
+}
+
+impl Library {
+    fn to_csv_records(&self) -> Vec<CsvRecord> {
+        let mut records: Vec<CsvRecord> = vec![];
+        for picture in &self.pictures {
+            records.push(CsvRecord {
+                file_path: Some(picture.file_path.clone()),
+                file_size: Some(picture.file_size),
+                colors: Some(picture.colors),
+                modified_time: Some(picture.modified_time),
+                rank: Some(picture.rank),
+                palette: Some(picture.palette.clone()),
+                label: Some(picture.label.clone()),
+                selected: Some(picture.selected),
+                deleted: Some(picture.deleted),
+                cover: Some(picture.cover),
+                content_hash: picture.content_hash.clone(),
+                perceptual_hash: picture.perceptual_hash,
+                note: Some(picture.note.clone()),
+                updated_time: picture.updated_time,
+                taken_time: picture.taken_time,
+                camera_make: picture.camera_make.clone(),
+                camera_model: picture.camera_model.clone(),
+                lens: picture.lens.clone(),
+                exposure: picture.exposure.clone(),
+                iso: picture.iso,
+                focal_length: picture.focal_length,
+                orientation: picture.orientation,
+                latitude: picture.latitude,
+                longitude: picture.longitude,
+                altitude: picture.altitude,
+                gps_read: Some(picture.gps_read),
+                width: picture.width,
+                height: picture.height,
+                frames: picture.frames,
+                ..CsvRecord::empty("Picture")
+            })
+        };
+        for tag in &self.tags {
+            records.push(CsvRecord {
+                file_path: Some(tag.file_path.clone()),
+                label: Some(tag.label.clone()),
+                ..CsvRecord::empty("Tag")
+            })
+        };
+        for cover in &self.covers {
+            records.push(CsvRecord {
+                dir_path: Some(cover.dir_path.clone()),
+                file_name: Some(cover.file_name.clone()),
+                rank: Some(cover.rank),
+                ..CsvRecord::empty("Cover")
+            })
+        };
+        records
+    }
+
+    fn from_csv_records(records: Vec<CsvRecord>) -> Result<Library> {
+        let mut library = Library::default();
+        for record in records {
+            let table = record.table.clone();
+            match table.as_str() {
+                "Picture" => library.pictures.push(PictureRecord {
+                    file_path: record.file_path.ok_or_else(|| missing(&table, "File_Path"))?,
+                    file_size: record.file_size.ok_or_else(|| missing(&table, "File_Size"))?,
+                    colors: record.colors.ok_or_else(|| missing(&table, "Colors"))?,
+                    modified_time: record.modified_time.ok_or_else(|| missing(&table, "Modified_Time"))?,
+                    rank: record.rank.ok_or_else(|| missing(&table, "Rank"))?,
+                    palette: record.palette.unwrap_or_default(),
+                    label: record.label.unwrap_or_default(),
+                    selected: record.selected.unwrap_or_default(),
+                    deleted: record.deleted.unwrap_or_default(),
+                    cover: record.cover.unwrap_or_default(),
+                    content_hash: record.content_hash,
+                    perceptual_hash: record.perceptual_hash,
+                    note: record.note.unwrap_or_default(),
+                    updated_time: record.updated_time,
+                    taken_time: record.taken_time,
+                    camera_make: record.camera_make,
+                    camera_model: record.camera_model,
+                    lens: record.lens,
+                    exposure: record.exposure,
+                    iso: record.iso,
+                    focal_length: record.focal_length,
+                    orientation: record.orientation,
+                    latitude: record.latitude,
+                    longitude: record.longitude,
+                    altitude: record.altitude,
+                    gps_read: record.gps_read.unwrap_or_default(),
+                    width: record.width,
+                    height: record.height,
+                    frames: record.frames,
+                }),
+                "Tag" => library.tags.push(TagRecord {
+                    file_path: record.file_path.ok_or_else(|| missing(&table, "File_Path"))?,
+                    label: record.label.ok_or_else(|| missing(&table, "Label"))?,
+                }),
+                "Cover" => library.covers.push(CoverRecord {
+                    dir_path: record.dir_path.ok_or_else(|| missing(&table, "Dir_Path"))?,
+                    file_name: record.file_name.ok_or_else(|| missing(&table, "File_Name"))?,
+                    rank: record.rank.ok_or_else(|| missing(&table, "Rank"))?,
+                }),
+                other => return Err(anyhow!(format!("unknown table {} in csv file", other))),
+            }
+        };
+        Ok(library)
+    }
+
+    pub fn write(&self, file_path: &str, format: Format) -> Result<()> {
+        let file = match File::create(file_path) {
+            Ok(file) => file,
+            Err(err) => return Err(anyhow!(format!("can't create {}: {}", file_path, err))),
+        };
+        match format {
+            Format::Json => match serde_json::to_writer_pretty(file, self) {
+                Ok(()) => Ok(()),
+                Err(err) => Err(anyhow!(err)),
+            },
+            Format::Csv => {
+                let mut writer = csv::Writer::from_writer(file);
+                for record in self.to_csv_records() {
+                    if let Err(err) = writer.serialize(record) {
+                        return Err(anyhow!(err))
+                    }
+                };
+                match writer.flush() {
+                    Ok(()) => Ok(()),
+                    Err(err) => Err(anyhow!(err)),
+                }
+            },
+        }
+    }
+
+    pub fn read(file_path: &str, format: Format) -> Result<Library> {
+        let file = match File::open(file_path) {
+            Ok(file) => file,
+            Err(err) => return Err(anyhow!(format!("can't open {}: {}", file_path, err))),
+        };
+        match format {
+            Format::Json => match serde_json::from_reader(file) {
+                Ok(library) => Ok(library),
+                Err(err) => Err(anyhow!(err)),
+            },
+            Format::Csv => {
+                let mut reader = csv::Reader::from_reader(file);
+                match reader.deserialize().collect::<Result<Vec<CsvRecord>, csv::Error>>() {
+                    Ok(records) => Library::from_csv_records(records),
+                    Err(err) => Err(anyhow!(err)),
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn my_library() -> Library {
+        Library {
+            pictures: vec![
+                PictureRecord {
+                    file_path: String::from("~/photos/sunset.jpg"),
+                    file_size: 4807,
+                    colors: 1234,
+                    modified_time: 1719000000,
+                    rank: 1,
+                    palette: String::from("1 2 3 4 5 6 7 8 9"),
+                    label: String::from("holidays, \"summer\""),
+                    selected: true,
+                    deleted: false,
+                    cover: true,
+                    content_hash: Some(String::from("af1349b9")),
+                    perceptual_hash: Some(-42),
+                    note: String::from("Anna and Léo, on the Côte d'Azur"),
+                    updated_time: Some(1719000100),
+                    taken_time: Some(1139931588),
+                    camera_make: Some(String::from("Canon")),
+                    camera_model: Some(String::from("Canon PowerShot G3")),
+                    lens: Some(String::from("5.4-21.6 mm")),
+                    exposure: Some(String::from("1/200 s")),
+                    iso: Some(50),
+                    focal_length: Some(7.2),
+                    orientation: Some(6),
+                    latitude: Some(43.6959),
+                    longitude: Some(7.2718),
+                    altitude: Some(12.5),
+                    gps_read: true,
+                    width: Some(2048),
+                    height: Some(1536),
+                    frames: Some(12),
+                },
+                PictureRecord {
+                    file_path: String::from("/photos/dawn.png"),
+                    file_size: 100,
+                    rank: 3,
+                    palette: String::from("0 0 0 0 0 0 0 0 0"),
+                    deleted: true,
+                    ..Default::default()
+                }],
+            tags: vec![TagRecord { file_path: String::from("~/photos/sunset.jpg"), label: String::from("sea") }],
+            covers: vec![CoverRecord { dir_path: String::from("~/photos"), file_name: String::from("sunset.jpg"), rank: 1 }],
+        }
+    }
+
+    #[test]
+    fn a_library_round_trips_in_both_formats() {
+        for (format, name) in [(Format::Json, "gsr_library_test.json"), (Format::Csv, "gsr_library_test.csv")] {
+            let file_path = std::env::temp_dir().join(name).display().to_string();
+            my_library().write(&file_path, format).unwrap();
+            assert_eq!(format, Format::of_file(&file_path));
+            assert_eq!(my_library(), Library::read(&file_path, format).unwrap());
+            let _ = std::fs::remove_file(&file_path);
+        }
+    }
+}
@@ -1,4 +1,3 @@
-use crate::path::replace_home_with_tilde;
 use anyhow::{anyhow,Result};
 use crate::Database;
 use crate::args::Args;
@@ -6,7 +5,7 @@ use crate::args::Operation;
 use crate::path::{check_file, get_picture_file_paths, standard_directory};
 use crate::picture_entry::PictureEntry;
 use crate::picture_entry::{PictureEntries};
-use regex::Regex;
+use crate::query::Query;
 use std::collections::HashSet;
 use std::path::PathBuf;
 
@@ -99,6 +98,19 @@ pub fn load_picture_entries_from_covers(database: &mut Database) -> Result<Pictu
     database.select_cover_picture_entries()
 }
 
+/// the pictures of the album, in the album order
+pub fn load_picture_entries_from_album(database: &mut Database, album: &str) -> Result<PictureEntries> {
+    println!("loading picture entries of album {}", album);
+    let mut picture_entries: PictureEntries = vec![];
+    for file_path in database.select_album(album)? {
+        match database.select_picture_entry(&file_path)? {
+            Some(picture_entry) => picture_entries.push(picture_entry),
+            None => eprintln!("{}: not in the database", file_path),
+        }
+    };
+    Ok(picture_entries)
+}
+
 pub fn load_picture_entries_from_directory_into_db(database: &mut Database, directory: &str, in_std_dir: bool) -> Result<PictureEntries> {
     println!("load_picture_entries_from_directory_into_db {}, {}", directory, in_std_dir);
     match database.insert_difference_from_directory(directory, in_std_dir) {
@@ -112,6 +124,8 @@ pub fn load_picture_entries_from_source(database: &mut Database, args: &Args) ->
         load_single_picture_entry(database, file)
     } else if args.covers {
         load_picture_entries_from_covers(database)
+    } else if let Some(album) = &args.album {
+        load_picture_entries_from_album(database, album)
     } else if let Some(Operation::AddFiles { source_dir }) = args.operation {
         match source_dir {
             Some(directory) => { load_picture_entries_from_directory_into_db(database, &directory, true) },
@@ -124,54 +138,56 @@ pub fn load_picture_entries_from_source(database: &mut Database, args: &Args) ->
     }
 }
 
+/// the query selecting the pictures to show, from the query, pattern and tag options
+pub fn selection_query(args: &Args) -> Result<Query> {
+    let mut query = match &args.query {
+        Some(source) => match Query::parse(source) {
+            Ok(query) => query,
+            Err(err) => return Err(anyhow!(err)),
+        },
+        None => Query::All,
+    };
+    if let Some(pattern) = &args.pattern {
+        query = query.and(Query::Path { pattern: pattern.clone() })
+    };
+    if let Some(tag_list) = &args.select {
+        query = query.and(Query::any_tag(tag_list))
+    };
+    if let Some(tag_list) = &args.include {
+        query = query.and(Query::all_tags(tag_list))
+    };
+    if let Some(source) = &args.tags_expr {
+        match Query::parse_tags(source) {
+            Ok(tags_query) => query = query.and(tags_query),
+            Err(err) => return Err(err),
+        }
+    };
+    if let Some(tag_list) = &args.exclude {
+        query = query.and(Query::Not(Box::new(Query::any_tag(tag_list))))
+    };
+    if let Some(text) = &args.search {
+        query = query.and(Query::Text { text: text.clone() })
+    };
+    Ok(query)
+}
+
 pub fn load_picture_entries_from_db(database: &mut Database, args: &Args) -> Result<PictureEntries> {
     println!("loading picture entries from database {:?}", database);
-    let args = args.clone();
-    let restriction = args.query.unwrap_or(String::from("true"));
-    let pattern = args.pattern.clone().map_or(String::from(""), |s| " and File_Path like '%".to_owned() + &s  + "%';");
-    let tag_select_set:HashSet<String> = match args.select {
-        Some(ref tag_list) =>  HashSet::from_iter(tag_list.iter().cloned()),
-        None => HashSet::new(),
-    };
-    let tag_include_set:HashSet<String> = match args.include {
-        Some(ref tag_list) =>  HashSet::from_iter(tag_list.iter().cloned()),
-        None => HashSet::new(),
+    let query = match selection_query(args) {
+        Ok(query) => query,
+        Err(err) => return Err(anyhow!(err)),
     };
-    match database.select_pictures(&(restriction + &pattern)) {
-        Ok(mut picture_entries) => {
-            let mut result:PictureEntries = vec![];
-            for picture_entry in &mut picture_entries {
-                let file_path = picture_entry.file_path.clone();
-                match database.entry_tags(&replace_home_with_tilde(&picture_entry.file_path)) {
-                    Ok(tags) => {
+    match database.select_pictures(&query) {
+        Ok(mut picture_entries) => match database.select_tags_by_file_path() {
+            Ok(mut tags_by_file_path) => {
+                for picture_entry in &mut picture_entries {
+                    if let Some(tags) = tags_by_file_path.remove(&picture_entry.file_path) {
                         picture_entry.image_data.tags = tags
-                    },
-                    Err(err) => return Err(anyhow!(err)),
-                    };
-                let entry_tags: HashSet<String>;
-                if !tag_select_set.is_empty() || !tag_include_set.is_empty() {
-                    match database.entry_tags(&file_path) {
-                        Ok(tags) => {
-                            entry_tags = HashSet::from_iter(tags.iter().cloned())
-                        },
-                        Err(err) => return Err(anyhow!(err)),
                     }
-                } else {
-                    entry_tags = HashSet::new()
-                }
-                let matches_select = match tag_select_set.len() {
-                    0 => true,
-                    _ => entry_tags.intersection(&tag_select_set).count() > 0,
-                };
-                let matches_include = match tag_include_set.len() {
-                    0 => true,
-                    _ => tag_include_set.is_subset(&entry_tags) ,
                 };
-                if matches_select && matches_include {
-                    result.push(picture_entry.clone())
-                }
-            };
-            Ok(result)
+                Ok(picture_entries)
+            },
+            Err(err) => Err(anyhow!(err)),
         },
         Err(err) => Err(anyhow!(err)),
     }
@@ -179,52 +195,27 @@ pub fn load_picture_entries_from_db(database: &mut Database, args: &Args) -> Res
 
 pub fn load_picture_entries_from_directory(database: &mut Database, directory: &str, args: &Args) -> Result<PictureEntries> {
     println!("loading picture entries in directory {}", directory);
-    let args = args.clone();
-    let tag_select_set:HashSet<String> = match args.select {
-        Some(ref tag_list) =>  HashSet::from_iter(tag_list.iter().cloned()),
-        None => HashSet::new(),
+    let query = match selection_query(args) {
+        Ok(query) => query,
+        Err(err) => return Err(anyhow!(err)),
     };
-    let tag_include_set:HashSet<String> = match args.include {
-        Some(ref tag_list) =>  HashSet::from_iter(tag_list.iter().cloned()),
-        None => HashSet::new(),
+    let tags_by_file_path = match database.select_tags_by_file_path() {
+        Ok(tags_by_file_path) => tags_by_file_path,
+        Err(err) => return Err(anyhow!(err)),
     };
+    let no_tags: HashSet<String> = HashSet::new();
     match get_picture_file_paths(directory) {
         Ok(file_paths) => {
             let mut errors = 0;
             let mut picture_entries: PictureEntries = vec![];
             for file_path in file_paths {
-                let matches_pattern = match args.pattern {
-                    None => true,
-                    Some(ref pattern) => {
-                        match Regex::new(pattern) {
-                            Ok(reg_expr) => reg_expr.captures(&file_path).is_some(),
-                            Err(err) => return Err(anyhow!(err)),
-                        }
-                    },
-                };
-                let entry_tags: HashSet<String>;
-                if !tag_select_set.is_empty() || !tag_include_set.is_empty() {
-                    match database.entry_tags(&file_path) {
-                        Ok(tags) => {
-                            entry_tags = HashSet::from_iter(tags.iter().cloned())
-                        },
-                        Err(err) => return Err(anyhow!(err)),
-                    }
-                } else {
-                    entry_tags = HashSet::new()
-                }
-                let matches_select = match tag_select_set.len() {
-                    0 => true,
-                    _ => entry_tags.intersection(&tag_select_set).count() > 0,
-                };
-                let matches_include = match tag_include_set.len() {
-                    0 => true,
-                    _ => tag_include_set.is_subset(&entry_tags) ,
-                };
-                if matches_pattern && matches_select && matches_include {
+                let entry_tags = tags_by_file_path.get(&file_path).unwrap_or(&no_tags);
+                if query.matches_file_path_and_tags(&file_path, entry_tags) != Some(false) {
                     match PictureEntry::from_file_or_database(&file_path, database) {
                         Ok(picture_entry) => {
-                            picture_entries.push(picture_entry)
+                            if query.matches(&picture_entry) {
+                                picture_entries.push(picture_entry)
+                            }
                         },
                         Err(err) => {
                             eprintln!("{}", err);
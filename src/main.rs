@@ -6,7 +6,7 @@ use crate::display::info;
 use crate::loader::check_database_and_files;
 use crate::path::copy_all_picture_files;
 use clap::Parser;
-use crate::args::Args;
+use crate::args::{Args, Operation, SearchAction, TagAction};
 use crate::catalog::Catalog;
 use glib::{clone};
 use crate::gui::{build_gui, startup_gui};
@@ -16,30 +16,55 @@ use std::process::exit;
 use std::cell::RefCell;
 use std::rc::Rc;
 use crate::commands::load_shortcuts;
+use crate::config::select_library;
 use crate::database::Database;
+use crate::library::{Format, Library};
+use crate::prompt::prompt_yes_no;
+use crate::retag::{replaced_tags, retag};
+use crate::search::{Filters, saved_search_args};
+use crate::sync::sync;
 
 mod args;
 mod catalog;
 mod commands;
 mod comment;
 mod completion;
+mod config;
 mod database;
 mod direction;
 mod display;
 mod editor;
+mod exif_data;
+mod fsck;
+mod geo;
 mod gui;
+mod history;
 mod image_data;
+mod keep;
+mod library;
 mod loader;
 mod navigator;
 mod order;
 mod palette;
 mod path;
+mod perceptual;
 mod picture_entry;
 mod picture_io;
 mod prompt;
+mod query;
 mod rank;
+mod retag;
+mod schema;
+mod search;
+mod sync;
 
 fn main() {
+    let mut args = Args::parse();
+    // select the library before its settings are read, exit if failed
+    if let Err(err) = select_library(args.library.as_deref()) {
+        println!("{}", err);
+        exit(1)
+    };
     // load command shortcuts from the .gallshkey.json file, exit if failed
     let shortcuts = match load_shortcuts() {
         Ok(result) => result,
@@ -49,15 +74,22 @@ fn main() {
         },
     };
 
-    let main_result = Args::parse().checked_args()
+    let main_result = args.checked_args()
         .and_then(|args| {
             println!("directory: {}", directory(args.clone().directory));
-            Database::initialize(args.create_schema)
+            Database::initialize()
                 .and_then(|mut database| {
                     match database_operations(database.borrow_mut(), &args) { 
                         Ok(_) => {},
                         Err(err) => return Err(anyhow!(err)),
                     };
+                    let args = match &args.saved {
+                        Some(name) => match saved_search_args(&database, name, &args) {
+                            Ok(saved_args) => saved_args,
+                            Err(err) => return Err(anyhow!(err)),
+                        },
+                        None => args.clone(),
+                    };
                     Catalog::init_catalog(&args)
                         .and_then(|mut catalog| {
                             if let Some(ref label) = args.label {
@@ -89,7 +121,7 @@ fn main() {
                                 info(&catalog);
                             };
                             if args.deduplicate.is_some() {
-                                match catalog.deduplicate_files(&args.deduplicate.unwrap()) {
+                                match catalog.deduplicate_files(&args.deduplicate.unwrap(), args.keep, args.dry_run) {
                                     Ok(()) => return Ok(()),
                                     Err(err) => return Err(anyhow!(err)),
                                 }
@@ -154,5 +186,138 @@ fn main() {
                 Err(err) => return Err(anyhow!(err)),
             }
         };
+        if let Some(Operation::Export { format, file }) = &args.operation {
+            match database.select_library().and_then(|library| {
+                library.write(file, *format).map(|_| library)
+            }) {
+                Ok(library) => {
+                    println!("{} pictures, {} tags and {} covers exported to {}", library.pictures.len(), library.tags.len(), library.covers.len(), file);
+                    exit(0)
+                },
+                Err(err) => return Err(anyhow!(err)),
+            }
+        };
+        if let Some(Operation::Fsck { repair }) = &args.operation {
+            match database.fsck(*repair) {
+                Ok((issues, repaired)) => {
+                    for issue in &issues {
+                        println!("{}", issue)
+                    };
+                    if *repair {
+                        println!("{} issues found, {} repaired", issues.len(), repaired)
+                    } else {
+                        println!("{} issues found", issues.len())
+                    };
+                    exit(0)
+                },
+                Err(err) => return Err(anyhow!(err)),
+            }
+        };
+        if let Some(Operation::Import { file, replace }) = &args.operation {
+            let library = match Library::read(file, Format::of_file(file)) {
+                Ok(library) => library,
+                Err(err) => return Err(anyhow!(err)),
+            };
+            if *replace {
+                match prompt_yes_no(&format!("replace all the pictures, tags and covers of the database with the {} pictures of {} ?", library.pictures.len(), file)) {
+                    Ok(Some('y')) | Ok(Some('Y')) => {},
+                    Ok(_) => exit(0),
+                    Err(err) => return Err(anyhow!(err)),
+                }
+            };
+            match database.import_library(&library, *replace) {
+                Ok(report) => {
+                    for conflict in &report.conflicts {
+                        println!("conflict: {}", conflict)
+                    };
+                    println!("{} pictures, {} tags and {} covers imported from {}, {} conflicts left as in the database",
+                        report.pictures, report.tags, report.covers, file, report.conflicts.len());
+                    exit(0)
+                },
+                Err(err) => return Err(anyhow!(err)),
+            }
+        };
+        if let Some(Operation::Search { action }) = &args.operation {
+            let result = match action {
+                SearchAction::Save { name } => database.save_search(name, &Filters::from_args(args))
+                    .map(|_| println!("search {} saved", name)),
+                SearchAction::List => database.select_saved_searches()
+                    .map(|searches| for (name, filters) in searches {
+                        println!("{}: {}", name, filters)
+                    }),
+                SearchAction::Delete { name } => database.delete_saved_search(name)
+                    .and_then(|deleted| if deleted { Ok(()) } else { Err(anyhow!(format!("no search saved as {}", name))) }),
+            };
+            match result {
+                Ok(()) => exit(0),
+                Err(err) => return Err(anyhow!(err)),
+            }
+        };
+        if let Some(Operation::Sync { mode }) = &args.operation {
+            match sync(database, *mode, args.dry_run) {
+                Ok(count) => {
+                    println!("{} pictures differing from their sidecar{}", count, if args.dry_run { "" } else { " synced" });
+                    exit(0)
+                },
+                Err(err) => return Err(anyhow!(err)),
+            }
+        };
+        if let Some(Operation::Tag { action: action @ (TagAction::Alias { .. } | TagAction::Unalias { .. } | TagAction::Aliases) }) = &args.operation {
+            let result = match action {
+                TagAction::Alias { alias, tag } => database.set_tag_alias(alias, tag)
+                    .map(|_| println!("{} stands for {}", alias, tag)),
+                TagAction::Unalias { alias } => database.delete_tag_alias(alias)
+                    .and_then(|deleted| if deleted { Ok(()) } else { Err(anyhow!(format!("no alias {}", alias))) }),
+                _ => database.select_tag_aliases()
+                    .map(|aliases| for (alias, tag) in aliases {
+                        println!("{} → {}", alias, tag)
+                    }),
+            };
+            match result {
+                Ok(()) => exit(0),
+                Err(err) => return Err(anyhow!(err)),
+            }
+        };
+        if let Some(Operation::Tag { action }) = &args.operation {
+            match replaced_tags(action).and_then(|(sources, target)| retag(database, &sources, target.as_deref())) {
+                Ok(picture_entries) => {
+                    println!("{} pictures retagged", picture_entries.len());
+                    exit(0)
+                },
+                Err(err) => return Err(anyhow!(err)),
+            }
+        };
+        if let Some(Operation::Reconcile { source_dir: Some(directory) }) = &args.operation {
+            match database.reconcile(directory) {
+                Ok(count) => {
+                    println!("{} pictures reconciled", count);
+                    exit(0)
+                },
+                Err(err) => return Err(anyhow!(err)),
+            }
+        };
+        if args.rehash {
+            println!("computing missing content hashes…");
+            match database.rehash() {
+                Ok(count) => {
+                    println!("{} pictures hashed", count);
+                },
+                Err(err) => return Err(anyhow!(err)),
+            };
+            println!("reading missing EXIF metadata…");
+            match database.read_missing_exif() {
+                Ok(count) => {
+                    println!("{} pictures read", count);
+                },
+                Err(err) => return Err(anyhow!(err)),
+            };
+            println!("reading missing dimensions…");
+            match database.read_missing_dimensions() {
+                Ok(count) => {
+                    println!("{} pictures measured", count);
+                },
+                Err(err) => return Err(anyhow!(err)),
+            }
+        };
         Ok(())
     }
@@ -2,18 +2,22 @@ use clap::builder::PossibleValue;
 
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub enum Order {
-    Colors, Date, Label, Name, Palette, Size, Value, Random,
+    Aspect, Colors, Date, Distance, Label, Name, Palette, Pixels, Size, Taken, Value, Random, Sequence,
 }
 
 pub fn from(s: &str) -> Option<Order> {
     match s {
+            "a" => Some(Order::Aspect),
             "c" => Some(Order::Colors),
             "d" => Some(Order::Date),
+            "g" => Some(Order::Distance),
             "l" => Some(Order::Label),
+            "m" => Some(Order::Pixels),
             "n" => Some(Order::Name),
             "p" => Some(Order::Palette),
             "r" => Some(Order::Random),
             "s" => Some(Order::Size),
+            "t" => Some(Order::Taken),
             "v" => Some(Order::Value),
             _ => None,
     }
@@ -27,7 +31,7 @@ impl std::fmt::Display for Order {
 
 impl clap::ValueEnum for Order {
     fn value_variants<'a>() -> &'a [Self] {
-        &[Order::Colors, Order::Date, Order::Name, Order::Random, Order::Size, Order::Value, Order::Palette, Order::Label]
+        &[Order::Colors, Order::Date, Order::Name, Order::Random, Order::Size, Order::Value, Order::Palette, Order::Label, Order::Taken, Order::Distance, Order::Pixels, Order::Aspect, Order::Sequence]
     }
 
     fn to_possible_value(&self) -> Option<PossibleValue> {
@@ -40,6 +44,11 @@ impl clap::ValueEnum for Order {
             Order::Size => PossibleValue::new("Size"),
             Order::Palette => PossibleValue::new("Palette"),
             Order::Label => PossibleValue::new("Label"),
+            Order::Taken => PossibleValue::new("Taken").help("the date pictures were taken, or modified if unknown"),
+            Order::Distance => PossibleValue::new("Distance").help("the distance from --origin or the current picture, pictures without location last"),
+            Order::Pixels => PossibleValue::new("Pixels").help("the number of pixels, pictures of unknown dimensions first"),
+            Order::Aspect => PossibleValue::new("Aspect").help("the width divided by the height, from tallest to widest"),
+            Order::Sequence => PossibleValue::new("Sequence").help("the order pictures were loaded in"),
         })
     }
 }
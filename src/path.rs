@@ -5,8 +5,11 @@ use std::env;
 use std::fs;
 use std::path::{Path,PathBuf};
 use walkdir::WalkDir;
+use crate::config::{setting, settings};
+use crate::picture_io::{format_name, is_supported, picture_format};
 
-const VALID_EXTENSIONS: [&str; 6] = ["jpg", "jpeg", "png", "JPG", "JPEG", "PNG"];
+// extensions of the files expected to be pictures, reported when their content can't be decoded
+const PICTURE_EXTENSIONS: [&str; 9] = ["jpg", "jpeg", "png", "gif", "webp", "tif", "tiff", "bmp", "avif"];
 
 pub const THUMB_SUFFIX: &str = "THUMB";
 pub const IMAGE_DATA: &str = "IMAGE_DATA";
@@ -18,6 +21,9 @@ pub const DEFAULT_EXTRACT_LIST_FILE_NAME: &str = "gsr_extract.txt";
 pub const ABSOLUTE_PATH: bool = true;
 
 pub fn default_extract_list_file() -> Result<String> {
+    if let Some(extract_file) = settings().extract_file {
+        return Ok(extract_file)
+    };
     match home_dir() {
         Some(mut path_buf) => {
             path_buf.push(DEFAULT_EXTRACT_LIST_FILE_NAME);
@@ -52,12 +58,7 @@ pub fn check_file(source: &str) -> Result<PathBuf> {
     } else {
         match fs::metadata(path.clone()) {
             Ok(_) => {
-                let valid_extension = match path.extension() {
-                    Some(extension) => VALID_EXTENSIONS.contains(&extension.to_str().unwrap()),
-                    None => false,
-                };
-                let not_a_thumbnail = matches!(path.to_str().map(|f| f.contains(THUMB_SUFFIX)), Some(false));
-                if path.is_file() && valid_extension && not_a_thumbnail {
+                if is_picture_file(&path) {
                     Ok(path)
                 } else {
                     Err(anyhow!(format!("{} is not a valid file", source)))
@@ -83,18 +84,13 @@ pub fn check_reading_list_file(source: &str) -> Result<PathBuf> {
 }
 
 // recursively collect all file paths from pictures in the <source> folder
-// filtering for files with valid extensions (jpeg,jpg,png) and not including "THUMB" in their name 
+// filtering for files whose content is a picture that can be decoded and not including "THUMB" in their name
 pub fn get_picture_file_paths(source: &str) -> Result<Vec<String>> {
     match check_path(source, ! ABSOLUTE_PATH) {
         Ok(directory) => {
             let mut file_paths: Vec<String> = Vec::new();
             for path in WalkDir::new(directory).into_iter().filter_map(|e| e.ok()).map(|e| e.into_path()) {
-                    let valid_extension = match path.extension() {
-                        Some(extension) => VALID_EXTENSIONS.contains(&extension.to_str().unwrap()),
-                        None => false,
-                    };
-                    let not_a_thumbnail = matches!(path.to_str().map(|f| f.contains(THUMB_SUFFIX)), Some(false));
-                    if path.is_file() && valid_extension && not_a_thumbnail {
+                    if is_picture_file(&path) {
                         file_paths.push((path.display()).to_string())
                     }
                 };
@@ -104,6 +100,37 @@ pub fn get_picture_file_paths(source: &str) -> Result<Vec<String>> {
     }
 }
 
+/// true if path is a picture file that can be decoded, whatever the case of its extension, and is
+/// not a thumbnail or a sidecar; files with a picture extension that can't be decoded are reported
+pub fn is_picture_file(path: &Path) -> bool {
+    let file_path = match path.to_str() {
+        Some(file_path) => file_path,
+        None => return false,
+    };
+    if !path.is_file() || file_path.contains(THUMB_SUFFIX) || file_path.contains(IMAGE_DATA) {
+        return false
+    };
+    match picture_format(file_path) {
+        Some(format) if is_supported(format) => true,
+        Some(format) => {
+            eprintln!("{} pictures are not supported: {}", format_name(format), file_path);
+            false
+        },
+        None => {
+            if has_picture_extension(path) {
+                eprintln!("{} is not a picture", file_path)
+            };
+            false
+        },
+    }
+}
+
+fn has_picture_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| PICTURE_EXTENSIONS.iter().any(|picture_extension| extension.eq_ignore_ascii_case(picture_extension)))
+}
+
 pub fn copy_all_picture_files(source: &str, target: &str) -> Result<()> {
     if source == target {
         return Err(anyhow!(format!("cannot copy pictures files from {} to {}", source, target)));
@@ -156,9 +183,21 @@ pub fn image_data_file_path(original_file_path: &str) -> String {
     new_path.to_str().unwrap().to_string()
 }
 
+pub fn thumbnail_file_path(original_file_path: &str) -> String {
+    let path = PathBuf::from(original_file_path);
+    let parent = path.parent().unwrap();
+    let file_stem = path.file_stem().unwrap();
+    let new_file_name = match path.extension() {
+        Some(extension) => format!("{}{}.{}", file_stem.to_str().unwrap(), THUMB_SUFFIX, extension.to_str().unwrap()),
+        None => format!("{}{}", file_stem.to_str().unwrap(), THUMB_SUFFIX),
+    };
+    let new_path = parent.join(new_file_name);
+    new_path.to_str().unwrap().to_string()
+}
+
 pub fn standard_directory() -> String {
-    let gallshdir = env::var(DIR_ENV_VAR);
-    if let Ok(standard_dir) = &gallshdir {
+    let gallshdir = setting(DIR_ENV_VAR, |settings| settings.directory.clone());
+    if let Some(standard_dir) = &gallshdir {
         String::from(standard_dir)
     } else {
         String::new()
@@ -166,20 +205,20 @@ pub fn standard_directory() -> String {
 }
 
 pub fn directory(directory: Option<String>) -> String {
-    let gallshdir = env::var(DIR_ENV_VAR);
+    let gallshdir = setting(DIR_ENV_VAR, |settings| settings.directory.clone());
     if let Some(directory_arg) = directory {
         directory_arg
-    } else if let Ok(standard_dir) = &gallshdir {
+    } else if let Some(standard_dir) = &gallshdir {
         String::from(standard_dir)
     } else {
-        println!("GALLSHDIR variable not set. Using {} as default.", DEFAULT_DIR);
+        println!("GALLSHDIR variable not set and no library directory. Using {} as default.", DEFAULT_DIR);
         String::from(DEFAULT_DIR)
     }
 }
 
 pub fn temp_directory() -> String {
-    let tmp_dir = env::var(TMP_ENV_VAR);
-    if let Ok(dir) = &tmp_dir {
+    let tmp_dir = setting(TMP_ENV_VAR, |settings| settings.temp_directory.clone());
+    if let Some(dir) = &tmp_dir {
         String::from(dir)
     } else {
         String::from(DEFAULT_TMP_DIR)
@@ -228,6 +267,26 @@ mod tests {
         assert_eq!(10, file_paths.len());
     }
 
+    #[test]
+    fn pictures_are_recognized_by_their_content_whatever_their_extension() {
+        let directory = std::env::temp_dir().join("gsr_picture_formats");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let image = image::DynamicImage::new_rgb8(8, 6);
+        for (file_name, format) in [("a.gif", image::ImageFormat::Gif), ("b.webp", image::ImageFormat::WebP), ("c.tif", image::ImageFormat::Tiff), ("d.bmp", image::ImageFormat::Bmp), ("e.jpg", image::ImageFormat::Png)] {
+            image.save_with_format(directory.join(file_name), format).unwrap()
+        };
+        copy("testdata/nature/flower.jpg", directory.join("f.Jpg")).unwrap();
+        fs::write(directory.join("g.jpg"), "not a picture").unwrap();
+        fs::write(directory.join("h.txt"), "not a picture either").unwrap();
+        let mut file_names: Vec<String> = get_picture_file_paths(directory.to_str().unwrap()).unwrap().iter().map(|file_path| file_name(file_path)).collect();
+        file_names.sort();
+        assert_eq!(vec!["a.gif", "b.webp", "c.tif", "d.bmp", "e.jpg", "f.Jpg"], file_names);
+        assert!(check_file(directory.join("g.jpg").to_str().unwrap()).is_err());
+        assert_eq!(directory.join("noextTHUMB").display().to_string(), thumbnail_file_path(directory.join("noext").to_str().unwrap()));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
     #[test]
     fn get_an_error_on_absent_directory() {
         let result = get_picture_file_paths("foo");
@@ -0,0 +1,94 @@
+use image::DynamicImage;
+use image::imageops::FilterType;
+
+pub type PerceptualHash = u64;
+
+/// difference hash: each bit tells if a pixel is brighter than its right neighbour
+/// in a 9×8 grayscale reduction of the picture, so that re-encoded or resized copies
+/// of a picture get the same or a close hash
+pub fn dhash(image: &DynamicImage) -> PerceptualHash {
+    let small = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash: PerceptualHash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | (left > right) as u64;
+        }
+    };
+    hash
+}
+
+/// number of differing bits between two hashes, from 0 (same picture) to 64
+pub fn hamming_distance(a: PerceptualHash, b: PerceptualHash) -> u32 {
+    (a ^ b).count_ones()
+}
+
+fn root(parents: &mut [usize], index: usize) -> usize {
+    let mut current = index;
+    while parents[current] != current {
+        parents[current] = parents[parents[current]];
+        current = parents[current]
+    };
+    current
+}
+
+/// groups of indices of hashes that are within threshold of each other, directly or through
+/// other members of the group; groups are ordered by their first index, single hashes are left out
+pub fn clusters(hashes: &[Option<PerceptualHash>], threshold: u32) -> Vec<Vec<usize>> {
+    let mut parents: Vec<usize> = (0..hashes.len()).collect();
+    for i in 0..hashes.len() {
+        if let Some(hash_i) = hashes[i] {
+            for (j, hash_j) in hashes.iter().enumerate().skip(i + 1) {
+                if let Some(hash_j) = hash_j
+                    && hamming_distance(hash_i, *hash_j) <= threshold {
+                    let (root_i, root_j) = (root(&mut parents, i), root(&mut parents, j));
+                    parents[root_j.max(root_i)] = root_j.min(root_i)
+                }
+            }
+        }
+    };
+    let mut groups: Vec<Vec<usize>> = vec![];
+    let mut group_of_root: Vec<Option<usize>> = vec![None; hashes.len()];
+    for index in 0..hashes.len() {
+        let root_index = root(&mut parents, index);
+        match group_of_root[root_index] {
+            Some(group) => groups[group].push(index),
+            None => {
+                group_of_root[root_index] = Some(groups.len());
+                groups.push(vec![index])
+            },
+        }
+    };
+    groups.into_iter().filter(|group| group.len() > 1).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb};
+
+    fn my_image(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(ImageBuffer::from_fn(width, height, |x, y| {
+            let value = ((x * 255 / width) ^ (y * 255 / height)) as u8;
+            Rgb([value, value / 2, 255 - value])
+        }))
+    }
+
+    #[test]
+    fn resized_copies_have_close_hashes() {
+        let original = my_image(640, 480);
+        let resized = original.resize_exact(320, 240, FilterType::Lanczos3);
+        let other = original.rotate90();
+        assert!(hamming_distance(dhash(&original), dhash(&resized)) <= 4);
+        assert!(hamming_distance(dhash(&original), dhash(&other)) > 10);
+    }
+
+    #[test]
+    fn clusters_are_transitive_and_leave_single_hashes_out() {
+        let hashes = vec![Some(0b0001), Some(0b1111_0000), Some(0b0011), None, Some(0b0111), Some(0b1111_0001)];
+        assert_eq!(vec![vec![0, 2, 4], vec![1, 5]], clusters(&hashes, 1));
+        assert_eq!(vec![vec![0, 2, 4]], clusters(&hashes[..5], 1));
+        assert!(clusters(&hashes, 0).is_empty());
+    }
+}
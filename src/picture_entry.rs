@@ -1,3 +1,4 @@
+use crate::exif_data::ExifData;
 use crate::image_data::ImageData;
 use itertools::Itertools;
 use std::collections::HashSet;
@@ -8,8 +9,8 @@ use std::cmp::Ordering;
 use std::cmp::Ordering::*;
 use std::time::SystemTime;
 use crate::rank::Rank;
-use crate::picture_io::{copy_file_to_target_directory, delete_file, read_or_create_image_data, read_file_info};
-use crate::path::{THUMB_SUFFIX, image_data_file_path, temp_directory};
+use crate::picture_io::{copy_file_to_target_directory, delete_file, read_dimensions, read_frame_count, read_or_create_image_data, read_file_info};
+use crate::path::{THUMB_SUFFIX, image_data_file_path, temp_directory, thumbnail_file_path};
 
 pub type PictureEntries = Vec<PictureEntry>;
 
@@ -20,6 +21,13 @@ pub struct PictureEntry {
     pub modified_time: SystemTime,
     pub deleted: bool,
     pub image_data: ImageData,
+    pub content_hash: Option<String>,
+    pub exif: ExifData,
+    /// width and height in pixels as stored in the file, before its EXIF orientation is applied
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// number of frames, more than one for an animated picture
+    pub frames: Option<u32>,
 }
 
 pub fn make_picture_entry(file_path: String, file_size: u64, modified_time: SystemTime, image_data: ImageData, deleted: bool) -> PictureEntry {
@@ -30,6 +38,11 @@ pub fn make_picture_entry(file_path: String, file_size: u64, modified_time: Syst
         modified_time,
         deleted,
         image_data: data,
+        content_hash: None,
+        exif: ExifData::default(),
+        width: None,
+        height: None,
+        frames: None,
     }
 }
 
@@ -47,12 +60,20 @@ impl PictureEntry {
         println!("retrieving picture from file {}", file_path);
         match read_file_info(file_path) {
             Ok((file_size, modified_time)) => match read_or_create_image_data(file_path) {
-                Ok(image_data) => Ok(make_picture_entry(
+                Ok(image_data) => {
+                    let mut picture_entry = make_picture_entry(
                         file_path.to_string(),
                         file_size,
                         modified_time,
                         image_data,
-                        false)),
+                        false);
+                    if let Ok((width, height)) = read_dimensions(file_path) {
+                        picture_entry.width = Some(width);
+                        picture_entry.height = Some(height)
+                    };
+                    picture_entry.frames = read_frame_count(file_path).ok();
+                    Ok(picture_entry)
+                },
                 Err(err) => Err(anyhow!(err)),
             },
             Err(err) => Err(err),
@@ -76,13 +97,15 @@ impl PictureEntry {
         } else {
             let path = PathBuf::from(self.file_path.clone());
             let parent = path.parent().unwrap();
-            let extension = path.extension().unwrap();
             let file_stem = path.file_stem().unwrap().to_str().unwrap();
             let new_file_stem = match file_stem.strip_suffix("THUMB") {
                 Some(s) => s,
                 None => file_stem,
             };
-            let new_file_name = format!("{}.{}", new_file_stem, extension.to_str().unwrap());
+            let new_file_name = match path.extension() {
+                Some(extension) => format!("{}.{}", new_file_stem, extension.to_str().unwrap()),
+                None => new_file_stem.to_string(),
+            };
             let new_path = parent.join(new_file_name);
             new_path.to_str().unwrap().to_string()
         }
@@ -92,13 +115,7 @@ impl PictureEntry {
         if self.file_path.contains(THUMB_SUFFIX) {
             self.file_path.to_string()
         } else {
-            let path = PathBuf::from(self.file_path.clone());
-            let parent = path.parent().unwrap();
-            let extension = path.extension().unwrap();
-            let file_stem = path.file_stem().unwrap();
-            let new_file_name = format!("{}{}.{}", file_stem.to_str().unwrap(), THUMB_SUFFIX, extension.to_str().unwrap());
-            let new_path = parent.join(new_file_name);
-            new_path.to_str().unwrap().to_string()
+            thumbnail_file_path(&self.file_path)
         }
     }
 
@@ -107,31 +124,6 @@ impl PictureEntry {
     }
 
 
-    pub fn equal_content(&self, other:&PictureEntry) -> Result<bool> {
-        if self.file_size == 0 {
-            return Ok(false);
-        }
-        if self.file_size != other.file_size {
-            return Ok(false);
-        };
-        match std::fs::read(self.file_path.clone()) {
-            Ok(self_bytes) => {
-                match std::fs::read(other.file_path.clone()) {
-                    Ok(other_bytes) => {
-                        for i in 0 .. self_bytes.len() {
-                            if self_bytes[i] != other_bytes[i] {
-                                return Ok(false)
-                            }
-                        };
-                        Ok(true)
-                    },
-                    Err(err) => Err(anyhow!(err)),
-                }
-            },
-            Err(err) => Err(anyhow!(err)),
-        }
-    }
-
     pub fn label(&self) -> Option<String> {
         if !self.image_data.label.is_empty() {
             Some(self.image_data.label.clone())
@@ -140,6 +132,39 @@ impl PictureEntry {
         }
     }
 
+    /// width and height in pixels of the picture as displayed, i.e. swapped if its EXIF orientation turns it
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        self.width.zip(self.height).map(|(width, height)| match self.exif.orientation {
+            Some(5..=8) => (height, width),
+            _ => (width, height),
+        })
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.frames.is_some_and(|frames| frames > 1)
+    }
+
+    /// number of pixels of the picture, 0 if unknown
+    pub fn pixels(&self) -> u64 {
+        self.dimensions().map(|(width, height)| width as u64 * height as u64).unwrap_or_default()
+    }
+
+    /// width divided by height of the picture as displayed, 0 if unknown
+    pub fn aspect_ratio(&self) -> f64 {
+        match self.dimensions() {
+            Some((width, height)) if height > 0 => width as f64 / height as f64,
+            _ => 0.0,
+        }
+    }
+
+    /// the time the picture was taken, or its modification time if it has no EXIF date, in seconds since epoch
+    pub fn taken_time(&self) -> i64 {
+        match self.exif.taken {
+            Some(taken) => taken,
+            None => self.modified_time.duration_since(std::time::UNIX_EPOCH).map(|duration| duration.as_secs() as i64).unwrap_or_default(),
+        }
+    }
+
     pub fn cmp_label(&self, other: &PictureEntry) -> Ordering {
         match self.label() {
             Some(label_a) => match other.label() {
@@ -171,6 +196,10 @@ impl PictureEntry {
         self.image_data.label = String::from("");
     }
 
+    pub fn set_note(&mut self, note: &str) {
+        self.image_data.note = note.trim().to_string()
+    }
+
     pub fn set_rank(&mut self, rank: Rank) {
         self.image_data.rank = rank
     }
@@ -210,7 +239,8 @@ impl PictureEntry {
     }
 
     pub fn label_display(&self, has_focus: bool) -> String {
-        format!("{}  {} {} {}  {}  {}",
+        format!("{}{}  {} {} {}  {}  {}",
+            if self.is_animated() { "⏵" } else { "" },
             if self.image_data.cover { "🌟"} else { "" },
             if has_focus { "▄" } else { "" },
             self.image_data.rank.show(),
@@ -231,9 +261,13 @@ impl PictureEntry {
     }
 
     pub fn title_display(self) -> String {
-        format!("{} {} {} [{} {} {}] {} {} {}",
+        format!("{} {}{} {} [{} {} {}] {} {} {}",
             if self.image_data.cover { "🌟" } else { "" },
             self.original_file_name(),
+            match self.frames {
+                Some(frames) if frames > 1 => format!(" ⏵{} frames", frames),
+                _ => String::new(),
+            },
             if self.image_data.selected { "✅" } else { "" },
             self.file_size,
             self.image_data.colors,
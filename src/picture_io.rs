@@ -1,22 +1,32 @@
 use std::collections::HashSet;
 use std::fs::remove_file;
-use thumbnailer::ThumbnailSize;
-use thumbnailer::create_thumbnails;
-use thumbnailer::error::ThumbResult;
 use anyhow::{anyhow,Result};
-use std::io::{BufReader};
-use std::ffi::OsStr;
-use std::time::SystemTime;
+use image::codecs::gif::GifDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, DynamicImage, Frames, ImageFormat, ImageReader, RgbaImage};
+use std::io::{BufReader, Read};
+use std::time::{Duration, SystemTime};
 use std::path::{Path,PathBuf};
-use crate::path::image_data_file_path;
+use crate::path::{image_data_file_path, thumbnail_file_path};
 use std::fs;
 use std::fs::{File, read_to_string};
 use crate::rank::Rank;
 use crate::image_data::ImageData;
 use crate::palette::{Colors, get_colors, Palette, get_palette};
+use crate::perceptual::{dhash, PerceptualHash};
 
 pub type FileSize = u64;
 
+// largest width and height of a thumbnail, in pixels
+const THUMBNAIL_SIZE: u32 = 128;
+
+// frames shown for less than this are shown for DEFAULT_FRAME_DELAY, as web browsers do
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+// enough of the beginning of a file to recognize the signature of every picture format
+const SIGNATURE_LENGTH: usize = 32;
+
 pub fn read_file_info(file_path: &str) -> Result<(FileSize, SystemTime)> {
    let path = PathBuf::from(file_path);
    match fs::metadata(path.clone()) {
@@ -30,13 +40,29 @@ pub fn read_file_info(file_path: &str) -> Result<(FileSize, SystemTime)> {
 }
 
 
+/// width and height in pixels of a picture file as stored, reading only its header
+pub fn read_dimensions(file_path: &str) -> Result<(u32, u32)> {
+    let dimensions = ImageReader::open(file_path)
+        .map_err(image::ImageError::IoError)
+        .and_then(|mut reader| {
+            if let Some(format) = picture_format(file_path) {
+                reader.set_format(format)
+            };
+            reader.into_dimensions()
+        });
+    match dimensions {
+        Ok(dimensions) => Ok(dimensions),
+        Err(err) => Err(anyhow!(format!("can't read the dimensions of {}: {}", file_path, err))),
+    }
+}
+
 pub fn read_or_create_image_data(file_path: &str) -> Result<ImageData> {
     let image_data_file_path = image_data_file_path(file_path);
     match read_image_data(&image_data_file_path) {
         Ok(image_data) => Ok(image_data),
         Err(_) => {
-            match get_palette_from_picture(file_path) {
-                Ok((palette, colors)) => {
+            match get_image_features(file_path) {
+                Ok((palette, colors, perceptual_hash)) => {
                     let image_data = ImageData{
                         colors,
                         rank: Rank::NoStar,
@@ -45,6 +71,8 @@ pub fn read_or_create_image_data(file_path: &str) -> Result<ImageData> {
                         label: String::from(""),
                         cover: false,
                         tags: HashSet::new(),
+                        note: String::new(),
+                        perceptual_hash: Some(perceptual_hash),
                     };
                     match write_image_data(&image_data, &image_data_file_path) {
                         Ok(()) => Ok(image_data),
@@ -57,6 +85,35 @@ pub fn read_or_create_image_data(file_path: &str) -> Result<ImageData> {
     }
 }
 
+/// hash of the content of a file, as an hexadecimal string
+pub fn content_hash(file_path: &str) -> Result<String> {
+    match File::open(file_path) {
+        Ok(file) => {
+            let mut hasher = blake3::Hasher::new();
+            match hasher.update_reader(BufReader::new(file)) {
+                Ok(_) => Ok(hasher.finalize().to_hex().to_string()),
+                Err(err) => Err(anyhow!(err)),
+            }
+        },
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+/// move the thumbnail and image data files of a picture along with the picture
+pub fn move_sidecar_files(old_file_path: &str, new_file_path: &str) -> Result<()> {
+    for (old_sidecar, new_sidecar) in [
+        (thumbnail_file_path(old_file_path), thumbnail_file_path(new_file_path)),
+        (image_data_file_path(old_file_path), image_data_file_path(new_file_path))] {
+        if !Path::new(&old_sidecar).exists() || Path::new(&new_sidecar).exists() {
+            continue
+        };
+        if let Err(err) = fs::rename(&old_sidecar, &new_sidecar) {
+            return Err(anyhow!(format!("can't move {} to {}: {}", old_sidecar, new_sidecar, err)))
+        }
+    };
+    Ok(())
+}
+
 pub fn delete_file(file_path: &str) -> Result<()> {
     let path = Path::new(file_path);
     if path.exists() {
@@ -124,71 +181,147 @@ pub fn append_to_extract_file(name: &str, extract_file_path: &str) -> Result<()>
     }
 }
 
+/// the format of a picture file, recognized from its first bytes whatever its extension; None if
+/// the file isn't a picture
+pub fn picture_format(file_path: &str) -> Option<ImageFormat> {
+    let mut signature = [0u8; SIGNATURE_LENGTH];
+    let length = File::open(file_path).and_then(|mut file| file.read(&mut signature)).ok()?;
+    image::guess_format(&signature[..length]).ok()
+}
+
+/// the picture decoded, its format recognized from its content; pictures in a format this build
+/// can't decode are reported as unsupported
+pub fn open_picture(file_path: &str) -> Result<DynamicImage> {
+    let format = match picture_format(file_path) {
+        Some(format) if is_supported(format) => format,
+        Some(format) => return Err(anyhow!(format!("{} pictures are not supported: {}", format_name(format), file_path))),
+        None => return Err(anyhow!(format!("{} is not a picture", file_path))),
+    };
+    let mut reader = match ImageReader::open(file_path) {
+        Ok(reader) => reader,
+        Err(err) => return Err(anyhow!(err)),
+    };
+    reader.set_format(format);
+    match reader.decode() {
+        Ok(image) => Ok(image),
+        Err(err) => Err(anyhow!(format!("can't decode picture {}: {}", file_path, err))),
+    }
+}
+
+/// true if pictures of the format can be decoded by this build; decoding AVIF pictures needs the
+/// avif feature, and the dav1d library
+pub fn is_supported(format: ImageFormat) -> bool {
+    format.reading_enabled() && (format != ImageFormat::Avif || cfg!(feature = "avif"))
+}
+
+/// the name of a picture format, as its usual extension in uppercase
+pub fn format_name(format: ImageFormat) -> String {
+    format.extensions_str().first().map(|extension| extension.to_uppercase()).unwrap_or_else(|| format!("{:?}", format))
+}
+
 pub fn get_palette_from_picture(file_path: &str) -> Result<(Palette,Colors)> {
+    get_image_features(file_path).map(|(palette, colors, _)| (palette, colors))
+}
+
+/// palette, colors and perceptual hash of a picture, opening it only once
+pub fn get_image_features(file_path: &str) -> Result<(Palette,Colors,PerceptualHash)> {
     eprintln!("getting palette for picture {}", file_path);
-    match image::open(file_path) {
+    match open_picture(file_path) {
         Ok(image) => {
             let palette = get_palette(&image);
             let colors = get_colors(&image);
-            Ok((palette,colors))
+            Ok((palette, colors, dhash(&image)))
         },
-        Err(_) => Err(anyhow!(format!("can't open image file {} for palette extraction", file_path))),
+        Err(err) => Err(anyhow!(format!("can't open image file {} for palette extraction: {}", file_path, err))),
     }
 }
 
-fn write_thumbnail<R: std::io::Seek + std::io::Read>(reader: BufReader<R>, extension: &str, mut output_file: File) -> ThumbResult<()> {
-    let mime = match extension {
-        "jpg" | "jpeg" | "JPG" | "JPEG" => mime::IMAGE_JPEG,
-        "png" | "PNG" => mime::IMAGE_PNG,
-        _ => panic!("wrong extension"),
-    };
-    let mut thumbnails = match create_thumbnails(reader, mime, [ThumbnailSize::Small]) {
-        Ok(tns) => tns,
-        Err(err) => {
-            eprintln!("error while creating thumbnails:{:?}", err);
-            return Err(err)
+pub fn get_perceptual_hash_from_picture(file_path: &str) -> Result<PerceptualHash> {
+    match open_picture(file_path) {
+        Ok(image) => Ok(dhash(&image)),
+        Err(err) => Err(anyhow!(format!("can't open image file {} for perceptual hash: {}", file_path, err))),
+    }
+}
+
+/// the picture decoded and turned upright according to its EXIF orientation
+pub fn oriented_image(file_path: &str, orientation: u16) -> Result<image::RgbaImage> {
+    match open_picture(file_path) {
+        Ok(mut image) => {
+            if let Some(orientation) = image::metadata::Orientation::from_exif(orientation as u8) {
+                image.apply_orientation(orientation)
+            };
+            Ok(image.into_rgba8())
         },
+        Err(err) => Err(anyhow!(format!("can't open image file {} for orientation: {}", file_path, err))),
+    }
+}
+
+// the frames of an animated GIF or WebP picture, None if the picture isn't in an animated format
+fn decoded_frames(file_path: &str) -> Result<Option<Frames<'static>>> {
+    let reader = match File::open(file_path) {
+        Ok(file) => BufReader::new(file),
+        Err(err) => return Err(anyhow!(err)),
     };
-    let thumbnail = thumbnails.pop().unwrap();
-    let write_result = match extension {
-        "jpg" | "jpeg" | "JPG" | "JPEG" => thumbnail.write_jpeg(&mut output_file,255),
-        "png" | "PNG" => thumbnail.write_png(&mut output_file),
-        _ => panic!("wrong extension"),
+    let frames = match picture_format(file_path) {
+        Some(ImageFormat::Gif) => GifDecoder::new(reader).map(|decoder| Some(decoder.into_frames())),
+        Some(ImageFormat::WebP) => WebPDecoder::new(reader).map(|decoder| if decoder.has_animation() { Some(decoder.into_frames()) } else { None }),
+        _ => Ok(None),
     };
-    match write_result {
-        Err(err) => {
-            eprintln!("error while writing thunbnail:{}", err);
-            Err(err)
-        },
-        ok => ok,
+    match frames {
+        Ok(frames) => Ok(frames),
+        Err(err) => Err(anyhow!(format!("can't decode the frames of {}: {}", file_path, err))),
     }
 }
 
+/// number of frames of a picture, more than one if it is an animated GIF or WebP picture
+pub fn read_frame_count(file_path: &str) -> Result<u32> {
+    match decoded_frames(file_path)? {
+        Some(frames) => Ok(frames.take_while(|frame| frame.is_ok()).count().max(1) as u32),
+        None => Ok(1),
+    }
+}
+
+/// the frames of an animated picture turned upright according to its EXIF orientation, with how
+/// long each of them is shown
+pub fn read_frames(file_path: &str, orientation: u16) -> Result<Vec<(RgbaImage, Duration)>> {
+    let frames = match decoded_frames(file_path)? {
+        Some(frames) => match frames.collect_frames() {
+            Ok(frames) => frames,
+            Err(err) => return Err(anyhow!(format!("can't decode the frames of {}: {}", file_path, err))),
+        },
+        None => return Err(anyhow!(format!("{} is not animated", file_path))),
+    };
+    let orientation = image::metadata::Orientation::from_exif(orientation as u8);
+    Ok(frames.into_iter().map(|frame| {
+        let (numerator, denominator) = frame.delay().numer_denom_ms();
+        let delay = Duration::from_millis(numerator as u64 / denominator.max(1) as u64);
+        let mut image = DynamicImage::ImageRgba8(frame.into_buffer());
+        if let Some(orientation) = orientation {
+            image.apply_orientation(orientation)
+        };
+        (image.into_rgba8(), if delay < MIN_FRAME_DELAY { DEFAULT_FRAME_DELAY } else { delay })
+    }).collect())
+}
+
 pub fn check_or_create_thumbnail_file(thumbnail_file_path: &str, original_file_path: &str) -> Result<()> {
     let path = PathBuf::from(thumbnail_file_path);
     if path.exists() {
         Ok(())
     } else {
-        match File::open(original_file_path) {
-            Err(err) => Err(anyhow!(err)),
-            Ok(input_file) => {
-                let source_path = Path::new(&original_file_path);
-                let extension = match source_path.extension()
-                    .and_then(OsStr::to_str) {
-                        None => return Err(anyhow!("source file has no extension")),
-                        Some(ext) => ext,
-                    };
-
-                let reader = BufReader::new(input_file);
-                let output_file = match File::create(thumbnail_file_path) {
-                    Err(err) => return Err(anyhow!(err)),
-                    Ok(file) => file,
-                };
-                match write_thumbnail(reader, extension, output_file) {
-                    Err(err) => Err(anyhow!(err)),
-                    Ok(_) => Ok(()),
-                }
-            },
+        if picture_format(original_file_path).is_none() {
+            return Err(anyhow!(format!("{} is not a picture", original_file_path)))
+        };
+        let thumbnail = open_picture(original_file_path)?.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+        // some formats can only be decoded: the thumbnail is written as a PNG if it has transparency,
+        // as a JPEG otherwise, whatever the extension of its picture
+        let result = if thumbnail.color().has_alpha() {
+            thumbnail.save_with_format(thumbnail_file_path, ImageFormat::Png)
+        } else {
+            DynamicImage::ImageRgb8(thumbnail.to_rgb8()).save_with_format(thumbnail_file_path, ImageFormat::Jpeg)
+        };
+        match result {
+            Ok(()) => Ok(()),
+            Err(err) => Err(anyhow!(format!("can't write thumbnail {}: {}", thumbnail_file_path, err))),
         }
     }
 }
@@ -232,6 +365,47 @@ mod tests {
         assert_eq!(expected, result.unwrap());
     }
 
+    #[test]
+    fn thumbnails_are_written_as_jpeg_or_as_png_with_transparency() {
+        let directory = std::env::temp_dir().join("gsr_thumbnails");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let file_path = directory.join("wide.webp").display().to_string();
+        image::DynamicImage::new_rgb8(512, 256).save_with_format(&file_path, ImageFormat::WebP).unwrap();
+        let thumbnail_path = thumbnail_file_path(&file_path);
+        assert!(check_or_create_thumbnail_file(&thumbnail_path, &file_path).is_ok());
+        assert_eq!(Some(ImageFormat::Jpeg), picture_format(&thumbnail_path));
+        assert_eq!((128, 64), read_dimensions(&thumbnail_path).unwrap());
+        let transparent_file_path = directory.join("logo.webp").display().to_string();
+        image::DynamicImage::new_rgba8(64, 64).save_with_format(&transparent_file_path, ImageFormat::WebP).unwrap();
+        let thumbnail_path = thumbnail_file_path(&transparent_file_path);
+        assert!(check_or_create_thumbnail_file(&thumbnail_path, &transparent_file_path).is_ok());
+        assert_eq!(Some(ImageFormat::Png), picture_format(&thumbnail_path));
+        let text_file_path = directory.join("text.png").display().to_string();
+        fs::write(&text_file_path, "not a picture").unwrap();
+        assert!(check_or_create_thumbnail_file(&thumbnail_file_path(&text_file_path), &text_file_path).is_err());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn frames_of_animated_pictures_are_counted_and_read() {
+        let directory = std::env::temp_dir().join("gsr_animations");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let file_path = directory.join("blink.gif").display().to_string();
+        let frames = [0u8, 255, 0].map(|level| image::Frame::from_parts(
+            RgbaImage::from_pixel(4, 2, image::Rgba([level, level, level, 255])), 0, 0,
+            image::Delay::from_numer_denom_ms(if level == 0 { 250 } else { 0 }, 1)));
+        image::codecs::gif::GifEncoder::new(File::create(&file_path).unwrap()).encode_frames(frames).unwrap();
+        assert_eq!(3, read_frame_count(&file_path).unwrap());
+        let frames = read_frames(&file_path, 6).unwrap();
+        assert_eq!(vec![Duration::from_millis(250), DEFAULT_FRAME_DELAY, Duration::from_millis(250)], frames.iter().map(|(_, delay)| *delay).collect::<Vec<Duration>>());
+        assert_eq!((2, 4), frames[0].0.dimensions());
+        assert_eq!(1, read_frame_count("testdata/nature/flower.jpg").unwrap());
+        assert!(read_frames("testdata/nature/flower.jpg", 1).is_err());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
     #[test]
     fn read_picture_file_info_read_file_size() {
         let result = read_file_info("testdata/nature/flower.jpg");
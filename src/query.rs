@@ -0,0 +1,747 @@
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use crate::geo::{checked_point, distance_km, parse_numbers, KM_PER_DEGREE};
+use crate::path::{is_prefix_path, replace_home_with_tilde, replace_tilde_with_home};
+use crate::picture_entry::PictureEntry;
+use rusqlite::types::Value;
+use std::collections::HashSet;
+use std::time::UNIX_EPOCH;
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+// width and height of a picture as displayed, its EXIF orientation turning it or not
+const DISPLAYED_WIDTH: &str = "(CASE WHEN Orientation BETWEEN 5 AND 8 THEN Height ELSE Width END)";
+const DISPLAYED_HEIGHT: &str = "(CASE WHEN Orientation BETWEEN 5 AND 8 THEN Width ELSE Height END)";
+
+/// Query selecting picture entries, parsed from expressions such as
+/// `rank>=2 and (tag:beach or label:travel) and not dir:~/images/2024`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Query {
+    /// Every picture
+    All,
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    /// Pictures having a number of stars compared to the given number
+    Rank { comparison: Comparison, stars: i64 },
+    /// Pictures with the given label
+    Label { label: String },
+    /// Pictures with the given tag, or with a tag below it such as place/france/paris for place/france
+    Tag { label: String },
+    /// Pictures in the given directory or its sub directories
+    Dir { directory: String },
+    /// Pictures whose file path contains the given string
+    Path { pattern: String },
+    /// Pictures modified on a day compared to the given day, in seconds since epoch
+    Modified { comparison: Comparison, day: i64 },
+    /// Pictures whose note contains the given string
+    Note { pattern: String },
+    /// Pictures whose path, label, tags or note have words starting with each word of the text
+    Text { text: String },
+    /// Pictures taken within radius kilometers of a point
+    Near { latitude: f64, longitude: f64, radius: f64 },
+    /// Pictures taken inside a box, from its south west to its north east corner; west may be
+    /// greater than east for a box across the 180th meridian
+    BoundingBox { south: f64, west: f64, north: f64, east: f64 },
+    /// Pictures whose displayed width in pixels compares to the given number
+    Width { comparison: Comparison, pixels: i64 },
+    /// Pictures whose displayed height in pixels compares to the given number
+    Height { comparison: Comparison, pixels: i64 },
+    /// Pictures whose number of pixels compares to the given number
+    Pixels { comparison: Comparison, pixels: i64 },
+    /// Pictures of the given shape
+    Shape { shape: Shape },
+    Selected,
+    Cover,
+    Deleted,
+}
+
+/// Landscape and portrait pictures are wider and taller than square ones, whose width and height
+/// differ by at most 1%
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Landscape, Portrait, Square,
+}
+
+impl Shape {
+    fn holds(&self, width: i64, height: i64) -> bool {
+        let square = (width - height).abs() * 100 <= width.max(height);
+        match self {
+            Shape::Landscape => width > height && !square,
+            Shape::Portrait => height > width && !square,
+            Shape::Square => square,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Equal, NotEqual, Less, LessOrEqual, Greater, GreaterOrEqual,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Open, Close, And, Or, Not, Word(String),
+}
+
+impl Comparison {
+    fn sql(&self) -> &str {
+        match self {
+            Comparison::Equal => "=",
+            Comparison::NotEqual => "<>",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+        }
+    }
+
+    fn holds(&self, a: i64, b: i64) -> bool {
+        match self {
+            Comparison::Equal => a == b,
+            Comparison::NotEqual => a != b,
+            Comparison::Less => a < b,
+            Comparison::LessOrEqual => a <= b,
+            Comparison::Greater => a > b,
+            Comparison::GreaterOrEqual => a >= b,
+        }
+    }
+}
+
+impl Query {
+
+    /// parse a query expression
+    pub fn parse(source: &str) -> Result<Query> {
+        parse_expression(source, false)
+            .map_err(|err| anyhow!(format!("query: {}", err)))
+    }
+
+    /// parse a tag expression where words are tags, e.g. `(beach or sea) and not blurry`
+    pub fn parse_tags(source: &str) -> Result<Query> {
+        parse_expression(source, true)
+            .map_err(|err| anyhow!(format!("tag expression: {}", err)))
+    }
+
+    /// conjunction of two queries
+    pub fn and(self, other: Query) -> Query {
+        match (self, other) {
+            (Query::All, query) | (query, Query::All) => query,
+            (a, b) => Query::And(Box::new(a), Box::new(b)),
+        }
+    }
+
+    /// pictures having at least one of the tags, every picture if there is no tag
+    pub fn any_tag(labels: &[String]) -> Query {
+        labels.iter()
+            .map(|label| Query::Tag { label: label.clone() })
+            .reduce(|a, b| Query::Or(Box::new(a), Box::new(b)))
+            .unwrap_or(Query::All)
+    }
+
+    /// pictures having all the tags
+    pub fn all_tags(labels: &[String]) -> Query {
+        labels.iter()
+            .fold(Query::All, |query, label| query.and(Query::Tag { label: label.clone() }))
+    }
+
+    /// compile the query into an SQL condition on the Picture table, pushing the values of its parameters
+    pub fn to_sql(&self, params: &mut Vec<Value>) -> String {
+        match self {
+            Query::All => String::from("true"),
+            Query::And(a, b) => {
+                let left = a.to_sql(params);
+                let right = b.to_sql(params);
+                format!("({} AND {})", left, right)
+            },
+            Query::Or(a, b) => {
+                let left = a.to_sql(params);
+                let right = b.to_sql(params);
+                format!("({} OR {})", left, right)
+            },
+            Query::Not(a) => format!("NOT ({})", a.to_sql(params)),
+            Query::Rank { comparison, stars } => {
+                params.push(Value::Integer(*stars));
+                format!("(3 - Rank) {} ?", comparison.sql())
+            },
+            Query::Label { label } => {
+                params.push(Value::Text(label.clone()));
+                String::from("Label = ?")
+            },
+            Query::Tag { label } => {
+                params.push(Value::Text(label.clone()));
+                params.push(Value::Text(format!("{}/", label)));
+                String::from("EXISTS (SELECT 1 FROM Tag WHERE Tag.File_Path = Picture.File_Path AND (Tag.Label = ? OR instr(Tag.Label, ?) = 1))")
+            },
+            Query::Dir { directory } => {
+                let prefix = directory_prefix(directory);
+                params.push(Value::Text(prefix.clone()));
+                params.push(Value::Text(replace_home_with_tilde(&prefix)));
+                String::from("(instr(File_Path, ?) = 1 OR instr(File_Path, ?) = 1)")
+            },
+            Query::Path { pattern } => {
+                params.push(Value::Text(pattern.clone()));
+                String::from("instr(File_Path, ?) > 0")
+            },
+            Query::Note { pattern } => {
+                params.push(Value::Text(pattern.clone()));
+                String::from("instr(COALESCE(Note, ''), ?) > 0")
+            },
+            Query::Modified { comparison, day } => {
+                let (lower, upper) = (*day, *day + SECONDS_PER_DAY);
+                let condition = match comparison {
+                    Comparison::Equal | Comparison::NotEqual => {
+                        params.push(Value::Integer(lower));
+                        params.push(Value::Integer(upper));
+                        String::from("(Modified_Time >= ? AND Modified_Time < ?)")
+                    },
+                    Comparison::Less => { params.push(Value::Integer(lower)); String::from("Modified_Time < ?") },
+                    Comparison::LessOrEqual => { params.push(Value::Integer(upper)); String::from("Modified_Time < ?") },
+                    Comparison::Greater => { params.push(Value::Integer(upper)); String::from("Modified_Time >= ?") },
+                    Comparison::GreaterOrEqual => { params.push(Value::Integer(lower)); String::from("Modified_Time >= ?") },
+                };
+                if *comparison == Comparison::NotEqual {
+                    format!("NOT {}", condition)
+                } else {
+                    condition
+                }
+            },
+            Query::Text { text } => match match_expression(text) {
+                Some(expression) => {
+                    params.push(Value::Text(expression));
+                    String::from("File_Path IN (SELECT File_Path FROM Picture_Search WHERE Picture_Search MATCH ?)")
+                },
+                None => String::from("true"),
+            },
+            Query::Near { latitude, longitude, radius } => {
+                // the latitude range of the circle narrows the search to the rows of an index range
+                let degrees = radius / KM_PER_DEGREE;
+                params.push(Value::Real(latitude - degrees));
+                params.push(Value::Real(latitude + degrees));
+                params.push(Value::Real(*latitude));
+                params.push(Value::Real(*longitude));
+                params.push(Value::Real(*radius));
+                String::from("(Latitude BETWEEN ? AND ? AND distance_km(Latitude, Longitude, ?, ?) <= ?)")
+            },
+            Query::BoundingBox { south, west, north, east } => {
+                params.push(Value::Real(*south));
+                params.push(Value::Real(*north));
+                params.push(Value::Real(*west));
+                params.push(Value::Real(*east));
+                if west <= east {
+                    String::from("(Latitude BETWEEN ? AND ? AND Longitude BETWEEN ? AND ?)")
+                } else {
+                    String::from("(Latitude BETWEEN ? AND ? AND (Longitude >= ? OR Longitude <= ?))")
+                }
+            },
+            Query::Width { comparison, pixels } => {
+                params.push(Value::Integer(*pixels));
+                format!("{} {} ?", DISPLAYED_WIDTH, comparison.sql())
+            },
+            Query::Height { comparison, pixels } => {
+                params.push(Value::Integer(*pixels));
+                format!("{} {} ?", DISPLAYED_HEIGHT, comparison.sql())
+            },
+            Query::Pixels { comparison, pixels } => {
+                params.push(Value::Integer(*pixels));
+                format!("Width * Height {} ?", comparison.sql())
+            },
+            Query::Shape { shape } => {
+                let square = String::from("abs(Width - Height) * 100 <= max(Width, Height)");
+                match shape {
+                    Shape::Landscape => format!("({} > {} AND NOT {})", DISPLAYED_WIDTH, DISPLAYED_HEIGHT, square),
+                    Shape::Portrait => format!("({} > {} AND NOT {})", DISPLAYED_HEIGHT, DISPLAYED_WIDTH, square),
+                    Shape::Square => square,
+                }
+            },
+            Query::Selected => String::from("Selected = True"),
+            Query::Cover => String::from("Cover = True"),
+            Query::Deleted => String::from("Deleted = True"),
+        }
+    }
+
+    /// evaluate the query on a picture entry
+    pub fn matches(&self, entry: &PictureEntry) -> bool {
+        match self {
+            Query::All => true,
+            Query::And(a, b) => a.matches(entry) && b.matches(entry),
+            Query::Or(a, b) => a.matches(entry) || b.matches(entry),
+            Query::Not(a) => !a.matches(entry),
+            Query::Rank { comparison, stars } => comparison.holds(3 - entry.image_data.rank as i64, *stars),
+            Query::Label { label } => entry.image_data.label == *label,
+            Query::Tag { label } => has_tag(&entry.image_data.tags, label),
+            Query::Note { pattern } => entry.image_data.note.contains(pattern.as_str()),
+            Query::Selected => entry.image_data.selected,
+            Query::Cover => entry.image_data.cover,
+            Query::Deleted => entry.deleted,
+            Query::Modified { comparison, day } => {
+                let time = entry.modified_time.duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+                let (lower, upper) = (*day, *day + SECONDS_PER_DAY);
+                match comparison {
+                    Comparison::Equal => time >= lower && time < upper,
+                    Comparison::NotEqual => time < lower || time >= upper,
+                    Comparison::Less => time < lower,
+                    Comparison::LessOrEqual => time < upper,
+                    Comparison::Greater => time >= upper,
+                    Comparison::GreaterOrEqual => time >= lower,
+                }
+            },
+            Query::Text { text } => {
+                let mut entry_words = search_words(&entry.original_file_path());
+                entry_words.extend(search_words(&entry.image_data.label));
+                for tag in &entry.image_data.tags {
+                    entry_words.extend(search_words(tag))
+                };
+                entry_words.extend(search_words(&entry.image_data.note));
+                search_words(text).iter().all(|word| entry_words.iter().any(|entry_word| entry_word.starts_with(word.as_str())))
+            },
+            Query::Near { latitude, longitude, radius } => entry.exif.location()
+                .is_some_and(|(entry_latitude, entry_longitude)| distance_km(*latitude, *longitude, entry_latitude, entry_longitude) <= *radius),
+            Query::BoundingBox { south, west, north, east } => entry.exif.location()
+                .is_some_and(|(latitude, longitude)| (*south..=*north).contains(&latitude) && if west <= east {
+                    (*west..=*east).contains(&longitude)
+                } else {
+                    longitude >= *west || longitude <= *east
+                }),
+            Query::Width { comparison, pixels } => entry.dimensions().is_some_and(|(width, _)| comparison.holds(width as i64, *pixels)),
+            Query::Height { comparison, pixels } => entry.dimensions().is_some_and(|(_, height)| comparison.holds(height as i64, *pixels)),
+            Query::Pixels { comparison, pixels } => entry.dimensions().is_some_and(|_| comparison.holds(entry.pixels() as i64, *pixels)),
+            Query::Shape { shape } => entry.dimensions().is_some_and(|(width, height)| shape.holds(width as i64, height as i64)),
+            Query::Dir { .. } | Query::Path { .. } => self.matches_file_path_and_tags(&entry.file_path, &entry.image_data.tags) == Some(true),
+        }
+    }
+
+    /// evaluate the query knowing only the file path and tags of a picture, None if the query can't be decided
+    pub fn matches_file_path_and_tags(&self, file_path: &str, tags: &HashSet<String>) -> Option<bool> {
+        match self {
+            Query::All => Some(true),
+            Query::And(a, b) => match (a.matches_file_path_and_tags(file_path, tags), b.matches_file_path_and_tags(file_path, tags)) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            },
+            Query::Or(a, b) => match (a.matches_file_path_and_tags(file_path, tags), b.matches_file_path_and_tags(file_path, tags)) {
+                (Some(true), _) | (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None,
+            },
+            Query::Not(a) => a.matches_file_path_and_tags(file_path, tags).map(|result| !result),
+            Query::Tag { label } => Some(has_tag(tags, label)),
+            Query::Dir { directory } => Some(is_prefix_path(&replace_tilde_with_home(directory), &replace_tilde_with_home(file_path))),
+            Query::Path { pattern } => Some(file_path.contains(pattern.as_str())),
+            _ => None,
+        }
+    }
+}
+
+// true if one of the tags is label or a tag below label
+fn has_tag(tags: &HashSet<String>, label: &str) -> bool {
+    tags.iter().any(|tag| tag == label || tag.strip_prefix(label).is_some_and(|rest| rest.starts_with('/')))
+}
+
+/// the lower case words of a text, split on what is not a letter or a digit as the full text index does
+pub fn search_words(text: &str) -> Vec<String> {
+    text.split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// the full text MATCH expression for pictures having words starting with each word of text, None if text has no word
+pub fn match_expression(text: &str) -> Option<String> {
+    let words = search_words(text);
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.iter().map(|word| format!("\"{}\"*", word)).collect::<Vec<String>>().join(" "))
+    }
+}
+
+fn directory_prefix(directory: &str) -> String {
+    let expanded = replace_tilde_with_home(directory);
+    format!("{}/", expanded.trim_end_matches('/'))
+}
+
+fn parse_expression(source: &str, tags: bool) -> Result<Query> {
+    match tokenize(source) {
+        Ok(tokens) => {
+            if tokens.is_empty() {
+                return Ok(Query::All)
+            };
+            let mut parser = Parser { tokens, position: 0, tags };
+            match parser.parse_or() {
+                Ok(query) => match parser.peek() {
+                    None => Ok(query),
+                    Some(token) => Err(anyhow!(format!("unexpected {:?} in {}", token, source))),
+                },
+                Err(err) => Err(anyhow!(format!("{} in {}", err, source))),
+            }
+        },
+        Err(err) => Err(err),
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens: Vec<Token> = vec![];
+    let mut chars = source.chars().peekable();
+    while let Some(&ch) = chars.peek() {
+        if ch.is_whitespace() {
+            chars.next();
+        } else if ch == '(' {
+            chars.next();
+            tokens.push(Token::Open)
+        } else if ch == ')' {
+            chars.next();
+            tokens.push(Token::Close)
+        } else {
+            let mut word = String::new();
+            let mut quoted = false;
+            let mut has_quotes = false;
+            while let Some(&ch) = chars.peek() {
+                if ch == '"' {
+                    quoted = !quoted;
+                    has_quotes = true;
+                } else if !quoted && (ch.is_whitespace() || ch == '(' || ch == ')') {
+                    break
+                } else {
+                    word.push(ch)
+                };
+                chars.next();
+            };
+            if quoted {
+                return Err(anyhow!(format!("unterminated quote in {}", source)))
+            };
+            tokens.push(match word.to_lowercase().as_str() {
+                "and" if !has_quotes => Token::And,
+                "or" if !has_quotes => Token::Or,
+                "not" if !has_quotes => Token::Not,
+                _ => Token::Word(word),
+            })
+        }
+    };
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    // words are tags instead of predicates
+    tags: bool,
+}
+
+impl Parser {
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Query> {
+        let mut query = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            let right = self.parse_and()?;
+            query = Query::Or(Box::new(query), Box::new(right))
+        };
+        Ok(query)
+    }
+
+    // two terms following each other without operator are a conjunction
+    fn parse_and(&mut self) -> Result<Query> {
+        let mut query = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => { self.next(); },
+                Some(Token::Not) | Some(Token::Open) | Some(Token::Word(_)) => {},
+                _ => break,
+            };
+            let right = self.parse_unary()?;
+            query = Query::And(Box::new(query), Box::new(right))
+        };
+        Ok(query)
+    }
+
+    fn parse_unary(&mut self) -> Result<Query> {
+        match self.next() {
+            Some(Token::Not) => self.parse_unary().map(|query| Query::Not(Box::new(query))),
+            Some(Token::Open) => {
+                let query = self.parse_or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(query),
+                    _ => Err(anyhow!("missing closing parenthesis")),
+                }
+            },
+            Some(Token::Word(word)) => if self.tags {
+                Ok(Query::Tag { label: word })
+            } else {
+                predicate(&word)
+            },
+            Some(token) => Err(anyhow!(format!("unexpected {:?}", token))),
+            None => Err(anyhow!("unexpected end of query")),
+        }
+    }
+}
+
+fn predicate(word: &str) -> Result<Query> {
+    match word.to_lowercase().as_str() {
+        "selected" => return Ok(Query::Selected),
+        "cover" => return Ok(Query::Cover),
+        "deleted" => return Ok(Query::Deleted),
+        "landscape" => return Ok(Query::Shape { shape: Shape::Landscape }),
+        "portrait" => return Ok(Query::Shape { shape: Shape::Portrait }),
+        "square" => return Ok(Query::Shape { shape: Shape::Square }),
+        _ => {},
+    };
+    if let Some(position) = word.find(['<', '>', '=', '!', ':']) {
+        let key = word[..position].to_lowercase();
+        let rest = &word[position..];
+        if let Some(value) = rest.strip_prefix(':') {
+            if value.is_empty() {
+                return Err(anyhow!(format!("missing value after {}", word)))
+            };
+            let value = value.to_string();
+            match key.as_str() {
+                "label" => Ok(Query::Label { label: value }),
+                "tag" => Ok(Query::Tag { label: value }),
+                "dir" => Ok(Query::Dir { directory: value }),
+                "path" => Ok(Query::Path { pattern: value }),
+                "text" => Ok(Query::Text { text: value }),
+                "note" => Ok(Query::Note { pattern: value }),
+                "near" => {
+                    let numbers = parse_numbers(&value, 3, "near")?;
+                    let (latitude, longitude) = checked_point(numbers[0], numbers[1], &value)?;
+                    if numbers[2] < 0.0 {
+                        return Err(anyhow!(format!("near radius should not be negative: {}", value)))
+                    };
+                    Ok(Query::Near { latitude, longitude, radius: numbers[2] })
+                },
+                "min-width" => Ok(Query::Width { comparison: Comparison::GreaterOrEqual, pixels: pixel_count(&value)? }),
+                "max-width" => Ok(Query::Width { comparison: Comparison::LessOrEqual, pixels: pixel_count(&value)? }),
+                "min-height" => Ok(Query::Height { comparison: Comparison::GreaterOrEqual, pixels: pixel_count(&value)? }),
+                "max-height" => Ok(Query::Height { comparison: Comparison::LessOrEqual, pixels: pixel_count(&value)? }),
+                "bbox" => {
+                    let numbers = parse_numbers(&value, 4, "bbox")?;
+                    let (south, west) = checked_point(numbers[0], numbers[1], &value)?;
+                    let (north, east) = checked_point(numbers[2], numbers[3], &value)?;
+                    if south > north {
+                        return Err(anyhow!(format!("bbox should be SOUTH,WEST,NORTH,EAST: {}", value)))
+                    };
+                    Ok(Query::BoundingBox { south, west, north, east })
+                },
+                _ => Err(anyhow!(format!("unknown predicate {}", key))),
+            }
+        } else {
+            let (comparison, value) = comparison(rest)?;
+            match key.as_str() {
+                "rank" => match value.parse::<i64>() {
+                    Ok(stars) if (0..=3).contains(&stars) => Ok(Query::Rank { comparison, stars }),
+                    _ => Err(anyhow!(format!("rank should be a number of stars from 0 to 3: {}", value))),
+                },
+                "width" => Ok(Query::Width { comparison, pixels: pixel_count(value)? }),
+                "height" => Ok(Query::Height { comparison, pixels: pixel_count(value)? }),
+                "megapixels" => match value.parse::<f64>() {
+                    Ok(megapixels) if megapixels.is_finite() && megapixels >= 0.0 => Ok(Query::Pixels { comparison, pixels: (megapixels * 1_000_000.0).round() as i64 }),
+                    _ => Err(anyhow!(format!("megapixels should be a positive number: {}", value))),
+                },
+                "modified" => match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+                    Ok(date) => Ok(Query::Modified { comparison, day: date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp() }),
+                    Err(_) => Err(anyhow!(format!("modified should be compared to a date YYYY-MM-DD: {}", value))),
+                },
+                _ => Err(anyhow!(format!("unknown predicate {}", key))),
+            }
+        }
+    } else {
+        Err(anyhow!(format!("unknown predicate {}", word)))
+    }
+}
+
+fn pixel_count(source: &str) -> Result<i64> {
+    match source.parse::<i64>() {
+        Ok(pixels) if pixels >= 0 => Ok(pixels),
+        _ => Err(anyhow!(format!("a number of pixels should be a positive integer: {}", source))),
+    }
+}
+
+fn comparison(source: &str) -> Result<(Comparison, &str)> {
+    let operators = [
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        ("!=", Comparison::NotEqual),
+        ("=", Comparison::Equal),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+    ];
+    for (operator, comparison) in operators {
+        if let Some(value) = source.strip_prefix(operator) {
+            if value.is_empty() {
+                return Err(anyhow!(format!("missing value after {}", operator)))
+            };
+            return Ok((comparison, value))
+        }
+    };
+    Err(anyhow!(format!("unknown operator in {}", source)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_data::ImageData;
+    use crate::picture_entry::make_picture_entry;
+    use crate::rank::Rank;
+    use std::time::{Duration, SystemTime};
+
+    fn my_entry(file_path: &str, rank: Rank, label: &str, tags: &[&str]) -> PictureEntry {
+        let day: SystemTime = UNIX_EPOCH + Duration::new(1704103957, 0); // 2024-01-01 10:12:37
+        make_picture_entry(String::from(file_path), 100, day, ImageData {
+            rank,
+            label: String::from(label),
+            tags: HashSet::from_iter(tags.iter().map(|tag| tag.to_string())),
+            ..Default::default()
+        }, false)
+    }
+
+    #[test]
+    fn parsing_operators_with_precedence() {
+        let query = Query::parse("tag:beach or tag:sea and not selected").unwrap();
+        assert_eq!(Query::Or(
+                Box::new(Query::Tag { label: String::from("beach") }),
+                Box::new(Query::And(
+                        Box::new(Query::Tag { label: String::from("sea") }),
+                        Box::new(Query::Not(Box::new(Query::Selected)))))), query);
+        let implicit = Query::parse("(rank>=2 label:travel)").unwrap();
+        assert_eq!(Query::And(
+                Box::new(Query::Rank { comparison: Comparison::GreaterOrEqual, stars: 2 }),
+                Box::new(Query::Label { label: String::from("travel") })), implicit);
+    }
+
+    #[test]
+    fn parsing_tag_expressions() {
+        let query = Query::parse_tags("(beach or sea) and not blurry").unwrap();
+        assert!(query.matches(&my_entry("/photos/foo.jpeg", Rank::NoStar, "", &["sea"])));
+        assert!(!query.matches(&my_entry("/photos/foo.jpeg", Rank::NoStar, "", &["sea", "blurry"])));
+        assert!(!query.matches(&my_entry("/photos/foo.jpeg", Rank::NoStar, "", &["mountain"])));
+        assert_eq!(Query::Tag { label: String::from("rank>=2") }, Query::parse_tags("rank>=2").unwrap());
+        assert!(Query::parse_tags("beach and (sea").is_err());
+    }
+
+    #[test]
+    fn parsing_errors_are_reported() {
+        assert!(Query::parse("(tag:beach").is_err());
+        assert!(Query::parse("rank>=5").is_err());
+        assert!(Query::parse("colour:red").is_err());
+        assert!(Query::parse("modified>yesterday").is_err());
+        assert!(Query::parse("label:\"foo").is_err());
+        assert!(Query::parse("tag:beach or").is_err());
+    }
+
+    #[test]
+    fn compiling_to_sql_with_parameters() {
+        let query = Query::parse("rank>=2 and not label:\"it's\"").unwrap();
+        let mut params = vec![];
+        assert_eq!("((3 - Rank) >= ? AND NOT (Label = ?))", query.to_sql(&mut params));
+        assert_eq!(vec![Value::Integer(2), Value::Text(String::from("it's"))], params);
+    }
+
+    #[test]
+    fn matching_picture_entries() {
+        let entry = my_entry("/photos/2024/foo.jpeg", Rank::TwoStars, "travel", &["beach"]);
+        assert!(Query::parse("rank>=2 and label:travel and tag:beach").unwrap().matches(&entry));
+        assert!(!Query::parse("rank=3 or tag:sea").unwrap().matches(&entry));
+        assert!(Query::parse("dir:/photos and not dir:/photos/2023").unwrap().matches(&entry));
+        assert!(Query::parse("modified=2024-01-01 and modified<2024-01-02 and modified>2023-12-31").unwrap().matches(&entry));
+        assert!(!Query::parse("modified>2024-01-01").unwrap().matches(&entry));
+    }
+
+    #[test]
+    fn matching_file_paths_and_tags_only_when_decidable() {
+        let query = Query::parse("path:foo and tag:beach and rank=3").unwrap();
+        let tags: HashSet<String> = HashSet::from([String::from("beach")]);
+        assert_eq!(Some(false), query.matches_file_path_and_tags("/photos/bar.jpeg", &tags));
+        assert_eq!(Some(false), query.matches_file_path_and_tags("/photos/foo.jpeg", &HashSet::new()));
+        assert_eq!(None, query.matches_file_path_and_tags("/photos/foo.jpeg", &tags));
+    }
+
+    #[test]
+    fn text_queries_match_word_prefixes() {
+        let entry = my_entry("/photos/Paris-2024/IMG_0001.jpeg", Rank::NoStar, "night walk", &["seine"]);
+        assert!(Query::Text { text: String::from("paris nig") }.matches(&entry));
+        assert!(Query::parse("text:sei and text:img").unwrap().matches(&entry));
+        assert!(!Query::Text { text: String::from("paris day") }.matches(&entry));
+        assert_eq!(Some(String::from("\"paris\"* \"nig\"*")), match_expression("Paris, nig\""));
+        assert_eq!(None, match_expression(" - "));
+    }
+
+    #[test]
+    fn note_queries_match_words_and_substrings_of_the_note() {
+        let mut entry = my_entry("/photos/IMG_0001.jpeg", Rank::NoStar, "", &[]);
+        entry.set_note("Anna's birthday, à Séville");
+        assert!(Query::parse("note:birthday").unwrap().matches(&entry));
+        assert!(Query::Text { text: String::from("anna sév") }.matches(&entry));
+        assert!(!Query::parse("note:wedding").unwrap().matches(&entry));
+        let mut params = vec![];
+        assert_eq!("instr(COALESCE(Note, ''), ?) > 0", Query::parse("note:birthday").unwrap().to_sql(&mut params));
+    }
+
+    #[test]
+    fn a_parent_tag_matches_the_tags_below_it() {
+        let entry = my_entry("/photos/foo.jpeg", Rank::NoStar, "", &["place/france/paris"]);
+        assert!(Query::Tag { label: String::from("place/france") }.matches(&entry));
+        assert!(Query::Tag { label: String::from("place/france/paris") }.matches(&entry));
+        assert!(!Query::Tag { label: String::from("place/fr") }.matches(&entry));
+        assert!(!Query::Tag { label: String::from("place/france/paris/louvre") }.matches(&entry));
+        let mut params = vec![];
+        Query::Tag { label: String::from("place") }.to_sql(&mut params);
+        assert_eq!(vec![Value::Text(String::from("place")), Value::Text(String::from("place/"))], params);
+    }
+
+    #[test]
+    fn location_queries_match_pictures_by_distance_or_box() {
+        let mut entry = my_entry("/photos/eiffel.jpeg", Rank::NoStar, "", &[]);
+        assert!(!Query::parse("near:48.8584,2.2945,5").unwrap().matches(&entry));
+        entry.exif.latitude = Some(48.8584);
+        entry.exif.longitude = Some(2.2945);
+        assert!(Query::parse("near:48.8606,2.3376,5").unwrap().matches(&entry));
+        assert!(!Query::parse("near:48.8606,2.3376,1").unwrap().matches(&entry));
+        assert!(Query::parse("bbox:48.8,2.2,48.9,2.4").unwrap().matches(&entry));
+        assert!(!Query::parse("bbox:48.9,2.2,49,2.4").unwrap().matches(&entry));
+        assert!(Query::parse("bbox:48,170,49,3").unwrap().matches(&entry));
+        assert!(Query::parse("near:48.8,2.3").is_err());
+        assert!(Query::parse("near:48.8,2.3,-1").is_err());
+        assert!(Query::parse("bbox:49,2.2,48,2.4").is_err());
+        let mut params = vec![];
+        assert_eq!("(Latitude BETWEEN ? AND ? AND distance_km(Latitude, Longitude, ?, ?) <= ?)", Query::parse("near:48.8606,2.3376,5").unwrap().to_sql(&mut params));
+        assert_eq!(Value::Real(5.0), params[4]);
+    }
+
+    #[test]
+    fn dimension_queries_take_the_orientation_into_account() {
+        let mut entry = my_entry("/photos/IMG_0001.jpeg", Rank::NoStar, "", &[]);
+        assert!(!Query::parse("landscape or portrait or square or width>0").unwrap().matches(&entry));
+        entry.width = Some(4000);
+        entry.height = Some(3000);
+        assert!(Query::parse("landscape and min-width:3000 and max-height:3000 and megapixels>=12").unwrap().matches(&entry));
+        assert!(!Query::parse("portrait or square or megapixels<12 or width!=4000").unwrap().matches(&entry));
+        entry.exif.orientation = Some(6);
+        assert!(Query::parse("portrait and width=3000 and height=4000").unwrap().matches(&entry));
+        entry.height = Some(3980);
+        assert!(Query::parse("square").unwrap().matches(&entry));
+        assert!(Query::parse("min-width:wide").is_err());
+        assert!(Query::parse("megapixels>-1").is_err());
+        let mut params = vec![];
+        assert_eq!("Width * Height < ?", Query::parse("megapixels<2.5").unwrap().to_sql(&mut params));
+        assert_eq!(vec![Value::Integer(2_500_000)], params);
+    }
+
+    #[test]
+    fn tag_lists_as_queries() {
+        let labels = vec![String::from("beach"), String::from("sea")];
+        let entry = my_entry("/photos/foo.jpeg", Rank::NoStar, "", &["sea"]);
+        assert!(Query::any_tag(&labels).matches(&entry));
+        assert!(!Query::all_tags(&labels).matches(&entry));
+        assert_eq!(Query::All, Query::any_tag(&[]));
+    }
+}
@@ -1,9 +1,9 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
-#[derive(PartialEq, Copy, Clone, Debug, Deserialize, Serialize)]
+#[derive(PartialEq, Copy, Clone, Debug, Default, Deserialize, Serialize)]
 pub enum Rank {
-   ThreeStars, TwoStars, OneStar, NoStar,
+   ThreeStars, TwoStars, OneStar, #[default] NoStar,
 }
 
 impl Rank {
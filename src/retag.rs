@@ -0,0 +1,80 @@
+use anyhow::{anyhow, Result};
+use crate::args::TagAction;
+use crate::database::Database;
+use crate::picture_entry::PictureEntry;
+use crate::picture_io::{read_image_data, write_image_data};
+use std::path::Path;
+
+/// true if label can be typed as a tag or a label in the gui: segments of lowercase letters,
+/// digits, - and _ separated by /, as in place/france/paris
+pub fn is_tag_label(label: &str) -> bool {
+    label.split('/').all(|segment| !segment.is_empty() && segment.chars().all(|ch| matches!(ch, 'a'..='z' | '0'..='9' | '-' | '_')))
+}
+
+/// the label, if it can be typed as a tag in the gui
+pub fn checked_label(label: &str) -> Result<String> {
+    if is_tag_label(label) {
+        Ok(label.to_string())
+    } else {
+        Err(anyhow!(format!("tag {} should be made of lowercase letters, digits, - and _, separated by /", label)))
+    }
+}
+
+/// the tags replaced by the action, and the tag replacing them if any
+pub fn replaced_tags(action: &TagAction) -> Result<(Vec<String>, Option<String>)> {
+    match action {
+        TagAction::Rename { old, new } => Ok((vec![old.clone()], Some(checked_label(new)?))),
+        TagAction::Merge { tags } => match tags.iter().position(|tag| tag.eq_ignore_ascii_case("into")) {
+            Some(position) if position > 0 && position == tags.len() - 2 => Ok((tags[..position].to_vec(), Some(checked_label(&tags[position + 1])?))),
+            _ => Err(anyhow!("tags to merge should be given as A B… into C")),
+        },
+        TagAction::Delete { tag } => Ok((vec![tag.clone()], None)),
+        TagAction::Alias { .. } | TagAction::Unalias { .. } | TagAction::Aliases => Err(anyhow!("aliases don't replace tags")),
+    }
+}
+
+/// replace the tags and labels sources with target, or remove them if target is None, in the
+/// database and in the sidecars of the pictures that have one; returns the pictures changed
+pub fn retag(database: &mut Database, sources: &[String], target: Option<&str>) -> Result<Vec<PictureEntry>> {
+    let file_paths = database.replace_tags(sources, target)?;
+    let mut picture_entries: Vec<PictureEntry> = vec![];
+    for file_path in file_paths {
+        if let Some(picture_entry) = database.select_picture_entry(&file_path)? {
+            let sidecar_file_path = picture_entry.image_data_file_path();
+            if Path::new(&sidecar_file_path).exists() {
+                let mut sidecar = read_image_data(&sidecar_file_path)?;
+                sidecar.label = picture_entry.image_data.label.clone();
+                sidecar.tags = picture_entry.image_data.tags.clone();
+                write_image_data(&sidecar, &sidecar_file_path)?
+            };
+            picture_entries.push(picture_entry)
+        }
+    };
+    Ok(picture_entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::{Args, Operation};
+    use clap::Parser;
+
+    fn action(arguments: &[&str]) -> TagAction {
+        match Args::parse_from(arguments).operation {
+            Some(Operation::Tag { action }) => action,
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn tag_actions_give_the_replaced_tags() {
+        assert_eq!((vec![String::from("beech")], Some(String::from("beach"))), replaced_tags(&action(&["gsr", "tag", "rename", "beech", "beach"])).unwrap());
+        assert_eq!((vec![String::from("sea"), String::from("ocean")], Some(String::from("water"))), replaced_tags(&action(&["gsr", "tag", "merge", "sea", "ocean", "INTO", "water"])).unwrap());
+        assert_eq!((vec![String::from("blurry")], None), replaced_tags(&action(&["gsr", "tag", "delete", "blurry"])).unwrap());
+        assert!(replaced_tags(&action(&["gsr", "tag", "merge", "sea", "ocean", "water"])).is_err());
+        assert!(replaced_tags(&action(&["gsr", "tag", "merge", "sea", "into", "water", "lake"])).is_err());
+        assert!(replaced_tags(&action(&["gsr", "tag", "rename", "beech", "Beach"])).is_err());
+        assert_eq!((vec![String::from("paris")], Some(String::from("place/france/paris"))), replaced_tags(&action(&["gsr", "tag", "rename", "paris", "place/france/paris"])).unwrap());
+        assert!(replaced_tags(&action(&["gsr", "tag", "rename", "paris", "place//paris"])).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+/// Migrations applied in order to the database schema by Database::initialize.
+/// The schema version of a database is the number of migrations applied to it:
+/// a migration must never be changed once released, only new ones appended.
+pub const MIGRATIONS: &[&str] = &[
+    // 1: initial schema
+    "CREATE TABLE IF NOT EXISTS Picture (    \n\
+        File_Path TEXT NOT NULL PRIMARY KEY, \n\
+        File_Size INTEGER,                   \n\
+        Colors INTEGER,                      \n\
+        Modified_Time INTEGER,               \n\
+        Rank INTEGER,                        \n\
+        Palette BLOB,                        \n\
+        Label TEXT,                          \n\
+        Selected BOOLEAN,                    \n\
+        Deleted BOOLEAN,                     \n\
+        Cover BOOLEAN);                      \n\
+    CREATE TABLE IF NOT EXISTS Tag (         \n\
+        File_Path TEXT NOT NULL,             \n\
+        Label TEXT NOT NULL,                 \n\
+        PRIMARY KEY ( File_Path, Label));    \n\
+    CREATE TABLE IF NOT EXISTS Cover (       \n\
+        Dir_Path TEXT NOT NULL,              \n\
+        File_Name TEXT NOT NULL,             \n\
+        Rank INTEGER,                        \n\
+        PRIMARY KEY (Dir_Path, File_Name));",
+    // 2: index on covers
+    "CREATE INDEX IF NOT EXISTS picture_cover ON Picture (Cover);",
+    // 3: content hash of the picture files
+    "ALTER TABLE Picture ADD COLUMN Content_Hash TEXT; \n\
+    CREATE INDEX IF NOT EXISTS picture_content_hash ON Picture (Content_Hash);",
+    // 4: perceptual hash of the pictures
+    "ALTER TABLE Picture ADD COLUMN Perceptual_Hash INTEGER;",
+    // 5: journal of the comments applied to pictures, for undo and redo
+    "CREATE TABLE IF NOT EXISTS History (       \n\
+        Id INTEGER PRIMARY KEY AUTOINCREMENT,   \n\
+        Batch INTEGER NOT NULL,                 \n\
+        File_Path TEXT NOT NULL,                \n\
+        Comment TEXT NOT NULL,                  \n\
+        Before TEXT NOT NULL,                   \n\
+        After TEXT NOT NULL,                    \n\
+        Deleted_Before BOOLEAN NOT NULL,        \n\
+        Deleted_After BOOLEAN NOT NULL,         \n\
+        Time INTEGER NOT NULL,                  \n\
+        Undone BOOLEAN NOT NULL DEFAULT FALSE); \n\
+    CREATE INDEX IF NOT EXISTS history_batch ON History (Batch);",
+    // 6: time of the last update of a picture, to sync with the sidecars
+    "ALTER TABLE Picture ADD COLUMN Updated_Time INTEGER;",
+    // 7: searches saved under a name, their filters being stored as json
+    "CREATE TABLE IF NOT EXISTS Saved_Search ( \n\
+        Name TEXT NOT NULL PRIMARY KEY,        \n\
+        Filters TEXT NOT NULL);",
+    // 8: albums, as lists of pictures ordered by position
+    "CREATE TABLE IF NOT EXISTS Album (     \n\
+        Name TEXT NOT NULL,                 \n\
+        Position INTEGER NOT NULL,          \n\
+        File_Path TEXT NOT NULL,            \n\
+        PRIMARY KEY (Name, File_Path));     \n\
+    CREATE INDEX IF NOT EXISTS album_position ON Album (Name, Position);",
+    // 9: full text index of the path, label, tags and note of the pictures, sharing the rowid of Picture
+    "CREATE VIRTUAL TABLE IF NOT EXISTS Picture_Search USING fts5(File_Path, Label, Tags, Note); \n\
+    INSERT INTO Picture_Search (rowid, File_Path, Label, Tags, Note)                             \n\
+        SELECT rowid, File_Path, COALESCE(Label, ''),                                           \n\
+            COALESCE((SELECT group_concat(Tag.Label, ' ') FROM Tag WHERE Tag.File_Path = Picture.File_Path), ''), '' \n\
+        FROM Picture;                                                                           \n\
+    CREATE TRIGGER IF NOT EXISTS picture_search_insert AFTER INSERT ON Picture BEGIN            \n\
+        INSERT INTO Picture_Search (rowid, File_Path, Label, Tags, Note)                         \n\
+        VALUES (NEW.rowid, NEW.File_Path, COALESCE(NEW.Label, ''),                              \n\
+            COALESCE((SELECT group_concat(Tag.Label, ' ') FROM Tag WHERE Tag.File_Path = NEW.File_Path), ''), ''); \n\
+    END;                                                                                        \n\
+    CREATE TRIGGER IF NOT EXISTS picture_search_update AFTER UPDATE OF File_Path, Label ON Picture BEGIN \n\
+        UPDATE Picture_Search SET File_Path = NEW.File_Path, Label = COALESCE(NEW.Label, ''),   \n\
+            Tags = COALESCE((SELECT group_concat(Tag.Label, ' ') FROM Tag WHERE Tag.File_Path = NEW.File_Path), '') \n\
+        WHERE rowid = NEW.rowid;                                                                \n\
+    END;                                                                                        \n\
+    CREATE TRIGGER IF NOT EXISTS picture_search_delete AFTER DELETE ON Picture BEGIN            \n\
+        DELETE FROM Picture_Search WHERE rowid = OLD.rowid;                                     \n\
+    END;                                                                                        \n\
+    CREATE TRIGGER IF NOT EXISTS tag_search_insert AFTER INSERT ON Tag BEGIN                    \n\
+        UPDATE Picture_Search                                                                   \n\
+        SET Tags = COALESCE((SELECT group_concat(Tag.Label, ' ') FROM Tag WHERE Tag.File_Path = NEW.File_Path), '') \n\
+        WHERE rowid IN (SELECT rowid FROM Picture WHERE File_Path = NEW.File_Path);             \n\
+    END;                                                                                        \n\
+    CREATE TRIGGER IF NOT EXISTS tag_search_delete AFTER DELETE ON Tag BEGIN                    \n\
+        UPDATE Picture_Search                                                                   \n\
+        SET Tags = COALESCE((SELECT group_concat(Tag.Label, ' ') FROM Tag WHERE Tag.File_Path = OLD.File_Path), '') \n\
+        WHERE rowid IN (SELECT rowid FROM Picture WHERE File_Path = OLD.File_Path);             \n\
+    END;                                                                                        \n\
+    CREATE TRIGGER IF NOT EXISTS tag_search_update AFTER UPDATE ON Tag BEGIN                    \n\
+        UPDATE Picture_Search                                                                   \n\
+        SET Tags = COALESCE((SELECT group_concat(Tag.Label, ' ') FROM Tag WHERE Tag.File_Path = Picture_Search.File_Path), '') \n\
+        WHERE rowid IN (SELECT rowid FROM Picture WHERE File_Path IN (OLD.File_Path, NEW.File_Path)); \n\
+    END;",
+    // 10: free text note of a picture, added to the full text index
+    "ALTER TABLE Picture ADD COLUMN Note TEXT;                                                   \n\
+    DROP TRIGGER IF EXISTS picture_search_insert;                                               \n\
+    DROP TRIGGER IF EXISTS picture_search_update;                                               \n\
+    CREATE TRIGGER picture_search_insert AFTER INSERT ON Picture BEGIN                          \n\
+        INSERT INTO Picture_Search (rowid, File_Path, Label, Tags, Note)                         \n\
+        VALUES (NEW.rowid, NEW.File_Path, COALESCE(NEW.Label, ''),                              \n\
+            COALESCE((SELECT group_concat(Tag.Label, ' ') FROM Tag WHERE Tag.File_Path = NEW.File_Path), ''), COALESCE(NEW.Note, '')); \n\
+    END;                                                                                        \n\
+    CREATE TRIGGER picture_search_update AFTER UPDATE OF File_Path, Label, Note ON Picture BEGIN \n\
+        UPDATE Picture_Search SET File_Path = NEW.File_Path, Label = COALESCE(NEW.Label, ''),   \n\
+            Tags = COALESCE((SELECT group_concat(Tag.Label, ' ') FROM Tag WHERE Tag.File_Path = NEW.File_Path), ''), \n\
+            Note = COALESCE(NEW.Note, '')                                                       \n\
+        WHERE rowid = NEW.rowid;                                                                \n\
+    END;",
+    // 11: aliases of tags, resolved when a tag is added in the gui
+    "CREATE TABLE IF NOT EXISTS Tag_Alias ( \n\
+        Alias TEXT NOT NULL PRIMARY KEY,    \n\
+        Tag TEXT NOT NULL);",
+    // 12: EXIF metadata of a picture, Orientation being NULL until the picture file is read
+    "ALTER TABLE Picture ADD COLUMN Taken_Time INTEGER; \n\
+    ALTER TABLE Picture ADD COLUMN Camera_Make TEXT;    \n\
+    ALTER TABLE Picture ADD COLUMN Camera_Model TEXT;   \n\
+    ALTER TABLE Picture ADD COLUMN Lens TEXT;           \n\
+    ALTER TABLE Picture ADD COLUMN Exposure TEXT;       \n\
+    ALTER TABLE Picture ADD COLUMN ISO INTEGER;         \n\
+    ALTER TABLE Picture ADD COLUMN Focal_Length REAL;   \n\
+    ALTER TABLE Picture ADD COLUMN Orientation INTEGER; \n\
+    CREATE INDEX IF NOT EXISTS picture_taken_time ON Picture (Taken_Time);",
+    // 13: GPS position of a picture
+    "ALTER TABLE Picture ADD COLUMN Latitude REAL;  \n\
+    ALTER TABLE Picture ADD COLUMN Longitude REAL;  \n\
+    ALTER TABLE Picture ADD COLUMN Altitude REAL;   \n\
+    CREATE INDEX IF NOT EXISTS picture_latitude ON Picture (Latitude);",
+    // 14: width and height in pixels of a picture as stored, NULL until the picture file is read
+    "ALTER TABLE Picture ADD COLUMN Width INTEGER;  \n\
+    ALTER TABLE Picture ADD COLUMN Height INTEGER;",
+    // 15: number of frames of a picture, more than one if it is animated, NULL until the picture file is read
+    "ALTER TABLE Picture ADD COLUMN Frames INTEGER;",
+    // 16: whether the GPS position of a picture was read, so that --rehash reads it for the pictures
+    // whose EXIF metadata was read before positions were stored
+    "ALTER TABLE Picture ADD COLUMN GPS_Read INTEGER NOT NULL DEFAULT 0; \n\
+    UPDATE Picture SET GPS_Read = 1 WHERE Latitude IS NOT NULL;",
+    // 17: full text index rows keyed by the file path of their picture rather than by its rowid,
+    // which VACUUM may renumber as Picture has a TEXT primary key
+    "DROP TRIGGER IF EXISTS picture_search_insert;                                              \n\
+    DROP TRIGGER IF EXISTS picture_search_update;                                               \n\
+    DROP TRIGGER IF EXISTS picture_search_delete;                                               \n\
+    DROP TRIGGER IF EXISTS tag_search_insert;                                                   \n\
+    DROP TRIGGER IF EXISTS tag_search_delete;                                                   \n\
+    DROP TRIGGER IF EXISTS tag_search_update;                                                   \n\
+    DROP TABLE IF EXISTS Picture_Search;                                                        \n\
+    CREATE VIRTUAL TABLE Picture_Search USING fts5(File_Path UNINDEXED, Path, Label, Tags, Note); \n\
+    INSERT INTO Picture_Search (File_Path, Path, Label, Tags, Note)                             \n\
+        SELECT File_Path, File_Path, COALESCE(Label, ''),                                       \n\
+            COALESCE((SELECT group_concat(Tag.Label, ' ') FROM Tag WHERE Tag.File_Path = Picture.File_Path), ''), COALESCE(Note, '') \n\
+        FROM Picture;                                                                           \n\
+    CREATE TRIGGER picture_search_insert AFTER INSERT ON Picture BEGIN                          \n\
+        INSERT INTO Picture_Search (File_Path, Path, Label, Tags, Note)                         \n\
+        VALUES (NEW.File_Path, NEW.File_Path, COALESCE(NEW.Label, ''),                          \n\
+            COALESCE((SELECT group_concat(Tag.Label, ' ') FROM Tag WHERE Tag.File_Path = NEW.File_Path), ''), COALESCE(NEW.Note, '')); \n\
+    END;                                                                                        \n\
+    CREATE TRIGGER picture_search_update AFTER UPDATE OF File_Path, Label, Note ON Picture BEGIN \n\
+        UPDATE Picture_Search SET File_Path = NEW.File_Path, Path = NEW.File_Path, Label = COALESCE(NEW.Label, ''), \n\
+            Tags = COALESCE((SELECT group_concat(Tag.Label, ' ') FROM Tag WHERE Tag.File_Path = NEW.File_Path), ''), \n\
+            Note = COALESCE(NEW.Note, '')                                                       \n\
+        WHERE File_Path = OLD.File_Path;                                                        \n\
+    END;                                                                                        \n\
+    CREATE TRIGGER picture_search_delete AFTER DELETE ON Picture BEGIN                          \n\
+        DELETE FROM Picture_Search WHERE File_Path = OLD.File_Path;                             \n\
+    END;                                                                                        \n\
+    CREATE TRIGGER tag_search_insert AFTER INSERT ON Tag BEGIN                                  \n\
+        UPDATE Picture_Search                                                                   \n\
+        SET Tags = COALESCE((SELECT group_concat(Tag.Label, ' ') FROM Tag WHERE Tag.File_Path = NEW.File_Path), '') \n\
+        WHERE File_Path = NEW.File_Path;                                                        \n\
+    END;                                                                                        \n\
+    CREATE TRIGGER tag_search_delete AFTER DELETE ON Tag BEGIN                                  \n\
+        UPDATE Picture_Search                                                                   \n\
+        SET Tags = COALESCE((SELECT group_concat(Tag.Label, ' ') FROM Tag WHERE Tag.File_Path = OLD.File_Path), '') \n\
+        WHERE File_Path = OLD.File_Path;                                                        \n\
+    END;                                                                                        \n\
+    CREATE TRIGGER tag_search_update AFTER UPDATE ON Tag BEGIN                                  \n\
+        UPDATE Picture_Search                                                                   \n\
+        SET Tags = COALESCE((SELECT group_concat(Tag.Label, ' ') FROM Tag WHERE Tag.File_Path = Picture_Search.File_Path), '') \n\
+        WHERE File_Path IN (OLD.File_Path, NEW.File_Path);                                      \n\
+    END;",
+];
+
+pub fn schema_version() -> usize {
+    MIGRATIONS.len()
+}
@@ -0,0 +1,131 @@
+use anyhow::{anyhow, Result};
+use crate::args::Args;
+use crate::database::Database;
+use crate::order::Order;
+use serde::{Deserialize, Serialize};
+
+/// the options selecting and ordering the pictures of a catalog, as saved under a name
+#[derive(PartialEq, Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Filters {
+    #[serde(default)]
+    pub query: Option<String>,
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub select: Option<Vec<String>>,
+    #[serde(default)]
+    pub include: Option<Vec<String>>,
+    #[serde(default)]
+    pub tags_expr: Option<String>,
+    #[serde(default)]
+    pub exclude: Option<Vec<String>>,
+    #[serde(default)]
+    pub search: Option<String>,
+    #[serde(default)]
+    pub order: Option<String>,
+    #[serde(default)]
+    pub origin: Option<String>,
+}
+
+impl Filters {
+    /// the filters of checked args
+    pub fn from_args(args: &Args) -> Filters {
+        Filters {
+            query: args.query.clone(),
+            pattern: args.pattern.clone(),
+            select: args.select.clone(),
+            include: args.include.clone(),
+            tags_expr: args.tags_expr.clone(),
+            exclude: args.exclude.clone(),
+            search: args.search.clone(),
+            order: Some(args.order.to_string()),
+            origin: args.origin.clone(),
+        }
+    }
+
+    /// args with their filters replaced by these filters
+    pub fn applied_to(&self, args: &Args) -> Result<Args> {
+        let order = match &self.order {
+            Some(name) => match <Order as clap::ValueEnum>::from_str(name, true) {
+                Ok(order) => order,
+                Err(err) => return Err(anyhow!(format!("unknown order {} in saved search: {}", name, err))),
+            },
+            None => args.order,
+        };
+        Ok(Args {
+            query: self.query.clone(),
+            pattern: self.pattern.clone(),
+            select: self.select.clone(),
+            include: self.include.clone(),
+            tags_expr: self.tags_expr.clone(),
+            exclude: self.exclude.clone(),
+            search: self.search.clone(),
+            order,
+            origin: self.origin.clone(),
+            ..args.clone()
+        })
+    }
+}
+
+impl std::fmt::Display for Filters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut options: Vec<String> = vec![];
+        if let Some(query) = &self.query { options.push(format!("--query \"{}\"", query)) };
+        if let Some(pattern) = &self.pattern { options.push(format!("--pattern \"{}\"", pattern)) };
+        if let Some(tags) = &self.select { options.push(format!("--select \"{}\"", tags.join(" "))) };
+        if let Some(tags) = &self.include { options.push(format!("--include \"{}\"", tags.join(" "))) };
+        if let Some(tags_expr) = &self.tags_expr { options.push(format!("--tags-expr \"{}\"", tags_expr)) };
+        if let Some(tags) = &self.exclude { options.push(format!("--exclude \"{}\"", tags.join(" "))) };
+        if let Some(text) = &self.search { options.push(format!("--search \"{}\"", text)) };
+        if let Some(order) = &self.order { options.push(format!("--order {}", order)) };
+        if let Some(origin) = &self.origin { options.push(format!("--origin {}", origin)) };
+        write!(f, "{}", options.join(" "))
+    }
+}
+
+/// true if name can be typed in the saved search input of the gui
+pub fn is_search_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|ch| matches!(ch, 'a'..='z' | '0'..='9' | '-' | '_'))
+}
+
+/// args with the filters of the search saved as name
+pub fn saved_search_args(database: &Database, name: &str, args: &Args) -> Result<Args> {
+    match database.select_saved_search(name) {
+        Ok(Some(filters)) => filters.applied_to(args),
+        Ok(None) => Err(anyhow!(format!("no search saved as {}", name))),
+        Err(err) => Err(anyhow!(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn saved_filters_replace_the_filters_of_args() {
+        let args = Args::parse_from(["gsr", "--query", "rank>=2", "--select", "sea beach", "--order", "date"]);
+        let filters = Filters {
+            select: Some(vec![String::from("sea"), String::from("beach")]),
+            ..Filters::from_args(&args)
+        };
+        assert_eq!(Some(String::from("Date")), filters.order);
+        let other = Args::parse_from(["gsr", "--pattern", "2024", "--width", "800"]);
+        let applied = filters.applied_to(&other).unwrap();
+        assert_eq!(Some(String::from("rank>=2")), applied.query);
+        assert_eq!(None, applied.pattern);
+        assert_eq!(Order::Date, applied.order);
+        assert_eq!(Some(800), applied.width);
+        assert_eq!(filters, serde_json::from_str(&serde_json::to_string(&filters).unwrap()).unwrap());
+        assert_eq!("--query \"rank>=2\" --select \"sea beach\" --order Date", filters.to_string());
+    }
+
+    #[test]
+    fn saving_a_search_takes_the_filters_given_after_the_name() {
+        let args = Args::parse_from(["gsr", "search", "save", "daily", "--query", "rank>=2", "--order", "name"]);
+        assert_eq!(Some(String::from("rank>=2")), args.query);
+        assert_eq!(Order::Name, args.order);
+        assert!(is_search_name("daily-2"));
+        assert!(!is_search_name("Daily"));
+    }
+}
@@ -0,0 +1,209 @@
+use anyhow::{anyhow, Result};
+use clap::builder::PossibleValue;
+use crate::database::Database;
+use crate::image_data::ImageData;
+use crate::picture_entry::PictureEntry;
+use crate::path::{file_name, file_path_directory};
+use crate::picture_io::{read_image_data, write_image_data};
+use crate::query::Query;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// which of the database and the IMAGE_DATA.json sidecars is taken as reference when syncing
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum SyncMode {
+    DbToSidecar, SidecarToDb, NewestWins,
+}
+
+impl std::fmt::Display for SyncMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match clap::ValueEnum::to_possible_value(self) {
+            Some(value) => write!(f, "{}", value.get_name()),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl clap::ValueEnum for SyncMode {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[SyncMode::DbToSidecar, SyncMode::SidecarToDb, SyncMode::NewestWins]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(match self {
+            SyncMode::DbToSidecar => PossibleValue::new("db-to-sidecar"),
+            SyncMode::SidecarToDb => PossibleValue::new("sidecar-to-db"),
+            SyncMode::NewestWins => PossibleValue::new("newest-wins").help("this is default"),
+        })
+    }
+}
+
+/// the direction a picture is synced in
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Direction {
+    ToSidecar, ToDb,
+}
+
+impl SyncMode {
+    /// direction of the sync given the update times in seconds of the database row and of the sidecar file
+    pub fn direction(&self, db_time: Option<i64>, sidecar_time: i64) -> Direction {
+        match self {
+            SyncMode::DbToSidecar => Direction::ToSidecar,
+            SyncMode::SidecarToDb => Direction::ToDb,
+            SyncMode::NewestWins => match db_time {
+                Some(time) if time < sidecar_time => Direction::ToDb,
+                // rows updated before update times were recorded hold curation the sidecars never got
+                _ => Direction::ToSidecar,
+            },
+        }
+    }
+}
+
+/// names of the curated fields that differ between the database and a sidecar
+pub fn differences(db: &ImageData, sidecar: &ImageData) -> Vec<&'static str> {
+    let mut result = vec![];
+    if db.rank != sidecar.rank { result.push("rank") };
+    if db.label != sidecar.label { result.push("label") };
+    if db.tags != sidecar.tags { result.push("tags") };
+    if db.selected != sidecar.selected { result.push("selected") };
+    if db.cover != sidecar.cover { result.push("cover") };
+    if db.note != sidecar.note { result.push("note") };
+    result
+}
+
+// the curated fields of source put in target, other fields being computed from the picture
+fn with_curation(target: &ImageData, source: &ImageData) -> ImageData {
+    ImageData {
+        rank: source.rank,
+        label: source.label.clone(),
+        tags: source.tags.clone(),
+        selected: source.selected,
+        cover: source.cover,
+        note: source.note.clone(),
+        ..target.clone()
+    }
+}
+
+// keep the Cover table in line with the cover flag and rank of a picture synced from its sidecar
+fn update_cover(database: &mut Database, file_path: &str, old: &ImageData, new: &ImageData) -> Result<()> {
+    let dir_path = file_path_directory(file_path);
+    let file_name = file_name(file_path);
+    if new.cover && (!old.cover || old.rank != new.rank) {
+        database.insert_or_update_cover(&dir_path, &file_name, new.rank)
+    } else if old.cover && !new.cover {
+        database.delete_cover(&dir_path, &file_name)
+    } else {
+        Ok(())
+    }
+}
+
+fn modified_seconds(file_path: &str) -> i64 {
+    fs::metadata(file_path)
+        .and_then(|metadata| metadata.modified())
+        .map(|time| time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs() as i64).unwrap_or_default())
+        .unwrap_or_default()
+}
+
+/// compare the pictures of the database with their sidecar, report the differences and,
+/// unless dry_run, update the sidecar or the database; returns the number of pictures that differ
+pub fn sync(database: &mut Database, mode: SyncMode, dry_run: bool) -> Result<usize> {
+    let mut picture_entries = match database.select_pictures(&Query::All) {
+        Ok(picture_entries) => picture_entries,
+        Err(err) => return Err(anyhow!(err)),
+    };
+    let mut tags_by_file_path = match database.select_tags_by_file_path() {
+        Ok(tags_by_file_path) => tags_by_file_path,
+        Err(err) => return Err(anyhow!(err)),
+    };
+    let updated_times = match database.select_updated_times() {
+        Ok(updated_times) => updated_times,
+        Err(err) => return Err(anyhow!(err)),
+    };
+    let mut count: usize = 0;
+    // the database updates are gathered, to be run in a single transaction once the sidecars are read
+    let mut updates: Vec<(PictureEntry, ImageData)> = vec![];
+    for picture_entry in &mut picture_entries {
+        if !Path::new(&picture_entry.original_file_path()).exists() {
+            continue
+        };
+        if let Some(tags) = tags_by_file_path.remove(&picture_entry.file_path) {
+            picture_entry.image_data.tags = tags
+        };
+        let sidecar_file_path = picture_entry.image_data_file_path();
+        let sidecar = read_image_data(&sidecar_file_path).ok();
+        let direction = match &sidecar {
+            Some(_) => mode.direction(updated_times.get(&picture_entry.file_path).copied().flatten(), modified_seconds(&sidecar_file_path)),
+            None => Direction::ToSidecar,
+        };
+        let changed = match &sidecar {
+            Some(sidecar) => differences(&picture_entry.image_data, sidecar),
+            None => vec!["sidecar missing"],
+        };
+        if changed.is_empty() || (sidecar.is_none() && mode == SyncMode::SidecarToDb) {
+            continue
+        };
+        count += 1;
+        println!("{}: {} {}", picture_entry.file_path, changed.join(", "), if direction == Direction::ToSidecar { "→ sidecar" } else { "→ database" });
+        if dry_run {
+            continue
+        };
+        let result = match (direction, &sidecar) {
+            (Direction::ToDb, Some(sidecar)) => {
+                let old_image_data = picture_entry.image_data.clone();
+                picture_entry.image_data = with_curation(&picture_entry.image_data, sidecar);
+                updates.push((picture_entry.clone(), old_image_data));
+                Ok(())
+            },
+            (_, Some(sidecar)) => write_image_data(&with_curation(sidecar, &picture_entry.image_data), &sidecar_file_path),
+            (_, None) => write_image_data(&picture_entry.image_data, &sidecar_file_path),
+        };
+        if let Err(err) = result {
+            return Err(anyhow!(err))
+        }
+    };
+    database.begin_transaction()?;
+    let result = updates.iter().try_for_each(|(picture_entry, old_image_data)| {
+        database.update_picture_entry(picture_entry)
+            .and_then(|_| update_cover(database, &picture_entry.file_path, old_image_data, &picture_entry.image_data))
+    });
+    database.end_transaction(result).map(|_| count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rank::Rank;
+    use std::collections::HashSet;
+
+    fn my_image_data(rank: Rank, label: &str, tags: &[&str]) -> ImageData {
+        ImageData {
+            colors: 42,
+            rank,
+            label: label.to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect::<HashSet<String>>(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn only_curated_fields_are_compared() {
+        let db = my_image_data(Rank::ThreeStars, "sunset", &["sea"]);
+        let mut sidecar = my_image_data(Rank::ThreeStars, "sunset", &["sea"]);
+        sidecar.colors = 7;
+        assert!(differences(&db, &sidecar).is_empty());
+        let sidecar = my_image_data(Rank::OneStar, "dawn", &["sea"]);
+        assert_eq!(vec!["rank", "label"], differences(&db, &sidecar));
+        assert_eq!(42, with_curation(&my_image_data(Rank::NoStar, "", &[]), &sidecar).colors);
+        assert!(differences(&with_curation(&db, &sidecar), &sidecar).is_empty());
+    }
+
+    #[test]
+    fn newest_wins_compares_update_times() {
+        assert_eq!(Direction::ToSidecar, SyncMode::NewestWins.direction(Some(200), 100));
+        assert_eq!(Direction::ToDb, SyncMode::NewestWins.direction(Some(100), 200));
+        assert_eq!(Direction::ToSidecar, SyncMode::NewestWins.direction(None, 100));
+        assert_eq!(Direction::ToSidecar, SyncMode::DbToSidecar.direction(None, 100));
+        assert_eq!(Direction::ToDb, SyncMode::SidecarToDb.direction(Some(200), 100));
+    }
+}